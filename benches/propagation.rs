@@ -0,0 +1,61 @@
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use wfc::{
+    image::load_image,
+    pattern8::Pattern8,
+    solver::Solver,
+    superposition::{ImageSuperposition, Wfc},
+};
+
+const SEED: u64 = 1987082627;
+
+fn create_superposition(size: u32, parallel: bool) -> ImageSuperposition<8, Pattern8> {
+    let mut image_sp = ImageSuperposition::<8, Pattern8>::with_seed(size, size, SEED);
+    image_sp.extract(load_image("./test/Water.png"));
+    image_sp.parallel = parallel;
+    image_sp
+}
+
+/// The initial propagation over the whole grid, sequentially and speculatively in
+/// parallel.
+fn propagate_all(c: &mut Criterion) {
+    let mut group = c.benchmark_group("propagate_all");
+    group.sample_size(10);
+
+    for size in [64, 256] {
+        for (name, parallel) in [("sequential", false), ("parallel", true)] {
+            let image_sp = create_superposition(size, parallel);
+            group.bench_with_input(BenchmarkId::new(name, size), &image_sp, |b, image_sp| {
+                b.iter_batched(
+                    || image_sp.clone(),
+                    |mut image_sp| image_sp.propagate_all(),
+                    criterion::BatchSize::LargeInput,
+                )
+            });
+        }
+    }
+
+    group.finish();
+}
+
+/// A full solve, where every collapse propagates from a single pixel.
+fn solve(c: &mut Criterion) {
+    let mut group = c.benchmark_group("solve");
+    group.sample_size(10);
+
+    for (name, parallel) in [("sequential", false), ("parallel", true)] {
+        let mut image_sp = create_superposition(48, parallel);
+        image_sp.propagate_all();
+        group.bench_with_input(BenchmarkId::new(name, 48), &image_sp, |b, image_sp| {
+            b.iter_batched(
+                || Solver::new(image_sp.clone()),
+                |mut solver| solver.run(),
+                criterion::BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, propagate_all, solve);
+criterion_main!(benches);
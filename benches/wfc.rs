@@ -0,0 +1,113 @@
+use std::hint::black_box;
+
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use wfc::{
+    image::{Image, load_image},
+    pattern8::Pattern8,
+    ruleset::Ruleset,
+    solver::Solver,
+    stack_set::StackSet,
+    superposition::{ImageSuperposition, Wfc},
+};
+
+/// Samples which solve without running into an unresolvable contradiction at `SEED`.
+const SAMPLES: [&str; 4] = ["Water", "flowers", "Skyline", "Village"];
+const SEED: u64 = 0;
+
+fn load_samples() -> Vec<(&'static str, Image)> {
+    SAMPLES
+        .iter()
+        .map(|&name| (name, load_image(format!("./test/{}.png", name))))
+        .collect()
+}
+
+fn create_superposition(sample: &Image, size: u32) -> ImageSuperposition<8, Pattern8> {
+    let mut image_sp = ImageSuperposition::<8, Pattern8>::with_seed(size, size, SEED);
+    image_sp.extract(sample.clone());
+    image_sp
+}
+
+fn extract(c: &mut Criterion) {
+    let mut group = c.benchmark_group("extract");
+    for (name, sample) in load_samples() {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &sample, |b, sample| {
+            b.iter(|| Ruleset::<8, Pattern8>::extract(sample))
+        });
+    }
+    group.finish();
+}
+
+fn propagate_all(c: &mut Criterion) {
+    let mut group = c.benchmark_group("propagate_all");
+    group.sample_size(10);
+    for (name, sample) in load_samples() {
+        let image_sp = create_superposition(&sample, 32);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(name),
+            &image_sp,
+            |b, image_sp| {
+                b.iter_batched(
+                    || image_sp.clone(),
+                    |mut image_sp| image_sp.propagate_all(),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+fn search(c: &mut Criterion) {
+    let mut group = c.benchmark_group("search");
+    for (name, sample) in load_samples() {
+        let mut image_sp = create_superposition(&sample, 64);
+        image_sp.propagate_all();
+        group.bench_with_input(
+            BenchmarkId::from_parameter(name),
+            &image_sp,
+            |b, image_sp| b.iter(|| image_sp.search()),
+        );
+    }
+    group.finish();
+}
+
+fn solve(c: &mut Criterion) {
+    let mut group = c.benchmark_group("solve");
+    group.sample_size(10);
+    for (name, sample) in load_samples() {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &sample, |b, sample| {
+            b.iter(|| Solver::<8, Pattern8>::from_sample(sample.clone(), 16, 16, SEED).run())
+        });
+    }
+    group.finish();
+}
+
+fn stack_set(c: &mut Criterion) {
+    const LEN: usize = 64 * 64;
+
+    let mut group = c.benchmark_group("stack_set");
+    group.bench_function("push_pop", |b| {
+        b.iter(|| {
+            let mut stack_set = StackSet::new(LEN);
+            for i in 0..LEN {
+                stack_set.push(i);
+                stack_set.push(i / 2);
+            }
+            while let Some(i) = stack_set.pop() {
+                black_box(i);
+            }
+        })
+    });
+    group.bench_function("full_pop", |b| {
+        b.iter(|| {
+            let mut stack_set = StackSet::full(LEN);
+            while let Some(i) = stack_set.pop() {
+                black_box(i);
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, extract, propagate_all, search, solve, stack_set);
+criterion_main!(benches);
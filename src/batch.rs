@@ -0,0 +1,342 @@
+use std::{
+    fmt, fs,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    cell::Cell,
+    image::{Image, Sample, try_load_sample, try_save_gray16, try_save_image},
+    indexed::IndexedImage,
+    pattern8::Pattern8,
+    quantize::Quantize,
+    solver::{Solver, Status},
+    stats::RunStats,
+};
+
+#[derive(Debug)]
+pub enum BatchError {
+    InvalidPattern(glob::PatternError),
+    NoSamples,
+    InvalidSeedRange(String),
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BatchError::InvalidPattern(err) => write!(f, "invalid sample pattern: {}", err),
+            BatchError::NoSamples => write!(f, "no samples found"),
+            BatchError::InvalidSeedRange(range) => write!(
+                f,
+                "invalid seed range '{}', expected e.g. '7', '0..10' or '0..=9'",
+                range
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+/// Generates an image for every combination of sample and seed.
+pub struct Batch {
+    pub samples: Vec<PathBuf>,
+    pub seeds: Range<u64>,
+    /// The output path, where `{name}` is replaced by the file stem of the sample
+    /// and `{seed}` by the seed.
+    pub output: String,
+    pub width: u32,
+    pub height: u32,
+    /// Applied to every sample before its ruleset is extracted.
+    pub quantize: Quantize,
+    /// Solve on the palette indices of the samples instead of their colors.
+    pub indexed: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchResult {
+    pub sample: PathBuf,
+    pub seed: u64,
+    pub output: PathBuf,
+    /// Wall time of the run including loading and saving.
+    pub seconds: f64,
+    /// Why the run failed, none if it was solved.
+    pub error: Option<String>,
+    pub stats: Option<RunStats>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchSummary {
+    pub successes: usize,
+    pub failures: usize,
+    pub seconds: f64,
+    pub results: Vec<BatchResult>,
+}
+
+impl Batch {
+    /// Runs all jobs in parallel on the global thread pool.
+    pub fn run(&self) -> BatchSummary {
+        let start = Instant::now();
+
+        let jobs: Vec<(&PathBuf, u64)> = self
+            .samples
+            .iter()
+            .flat_map(|sample| self.seeds.clone().map(move |seed| (sample, seed)))
+            .collect();
+        let results: Vec<BatchResult> = jobs
+            .into_par_iter()
+            .map(|(sample, seed)| self.run_job(sample, seed))
+            .collect();
+
+        let successes = results
+            .iter()
+            .filter(|result| result.error.is_none())
+            .count();
+        BatchSummary {
+            successes,
+            failures: results.len() - successes,
+            seconds: start.elapsed().as_secs_f64(),
+            results,
+        }
+    }
+
+    fn run_job(&self, sample: &Path, seed: u64) -> BatchResult {
+        let start = Instant::now();
+        let output = output_path(&self.output, sample, seed);
+
+        let mut stats = None;
+        let error = self.generate(sample, seed, &output, &mut stats).err();
+        match &error {
+            None => log::info!(
+                "{} with seed {}: {}",
+                sample.display(),
+                seed,
+                output.display()
+            ),
+            Some(error) => log::warn!("{} with seed {}: {}", sample.display(), seed, error),
+        }
+
+        BatchResult {
+            sample: sample.to_path_buf(),
+            seed,
+            output,
+            seconds: start.elapsed().as_secs_f64(),
+            error,
+            stats,
+        }
+    }
+
+    fn generate(
+        &self,
+        sample: &Path,
+        seed: u64,
+        output: &Path,
+        stats: &mut Option<RunStats>,
+    ) -> Result<(), String> {
+        let (mut image, format) = match try_load_sample(sample).map_err(|err| err.to_string())? {
+            Sample::Colors(image, format) => (image, format),
+            // the values of 16 bit gray are cells of their own, which are no colors
+            // to quantize and need no palette indices
+            Sample::Gray16(_) if self.quantize.is_enabled() => {
+                return Err(String::from("16 bit gray cannot be quantized"));
+            }
+            Sample::Gray16(heightmap) => {
+                let solver = self.solve(heightmap, seed, stats)?;
+                create_parent_dir(output)?;
+                return try_save_gray16(&solver.image_sp.to_image(0, 0), output)
+                    .map_err(|err| err.to_string());
+            }
+        };
+        if self.quantize.is_enabled() {
+            let (quantized, palette) = self.quantize.apply(image);
+            log::debug!("{}: {} colors", sample.display(), palette.colors.len());
+            image = quantized;
+        }
+
+        let image = if self.indexed {
+            let indexed = IndexedImage::from_image(&image).map_err(|err| err.to_string())?;
+            let solver = self.solve(indexed.to_index_map(), seed, stats)?;
+            indexed.render(&solver.image_sp)
+        } else {
+            let solver = self.solve(image, seed, stats)?;
+            Image::from(&solver.image_sp)
+        };
+
+        create_parent_dir(output)?;
+        try_save_image(&image, output, format).map_err(|err| err.to_string())
+    }
+
+    fn solve<C: Cell>(
+        &self,
+        sample: Image<C>,
+        seed: u64,
+        stats: &mut Option<RunStats>,
+    ) -> Result<Solver<8, Pattern8<C>>, String> {
+        let mut solver =
+            Solver::<8, Pattern8<C>>::from_sample(sample, self.width, self.height, seed);
+        let status = solver.run();
+        *stats = Some(solver.stats.clone());
+        if status != Status::Solved {
+            return Err(String::from("contradiction"));
+        }
+        Ok(solver)
+    }
+}
+
+fn create_parent_dir(path: &Path) -> Result<(), String> {
+    match path.parent() {
+        Some(dir) => fs::create_dir_all(dir).map_err(|err| err.to_string()),
+        None => Ok(()),
+    }
+}
+
+/// Expands the glob patterns, e.g. `test/*.png`, into a sorted list of files.
+pub fn find_samples(patterns: &[String]) -> Result<Vec<PathBuf>, BatchError> {
+    let mut samples = Vec::new();
+    for pattern in patterns {
+        let paths = glob::glob(pattern).map_err(BatchError::InvalidPattern)?;
+        samples.extend(paths.filter_map(Result::ok).filter(|path| path.is_file()));
+    }
+
+    samples.sort();
+    samples.dedup();
+    if samples.is_empty() {
+        return Err(BatchError::NoSamples);
+    }
+    Ok(samples)
+}
+
+/// Parses a single seed like `7`, a half-open range like `0..10` or an inclusive
+/// range like `0..=9`.
+pub fn parse_seed_range(range: &str) -> Result<Range<u64>, BatchError> {
+    let invalid = || BatchError::InvalidSeedRange(String::from(range));
+    let parse = |seed: &str| seed.trim().parse::<u64>().map_err(|_| invalid());
+
+    let seeds = if let Some((start, end)) = range.split_once("..=") {
+        parse(start)?..parse(end)?.checked_add(1).ok_or_else(invalid)?
+    } else if let Some((start, end)) = range.split_once("..") {
+        parse(start)?..parse(end)?
+    } else {
+        let seed = parse(range)?;
+        seed..seed.checked_add(1).ok_or_else(invalid)?
+    };
+
+    if seeds.is_empty() {
+        return Err(invalid());
+    }
+    Ok(seeds)
+}
+
+pub fn output_path(template: &str, sample: &Path, seed: u64) -> PathBuf {
+    let name = sample
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+
+    PathBuf::from(
+        template
+            .replace("{name}", &name)
+            .replace("{seed}", &seed.to_string()),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{color::Color, image::save_image};
+
+    #[test]
+    fn it_parses_seed_ranges() {
+        assert_eq!(parse_seed_range("7").unwrap(), 7..8);
+        assert_eq!(parse_seed_range("0..10").unwrap(), 0..10);
+        assert_eq!(parse_seed_range("3..=5").unwrap(), 3..6);
+        assert!(parse_seed_range("5..5").is_err());
+        assert!(parse_seed_range("a..b").is_err());
+    }
+
+    #[test]
+    fn it_fills_in_the_output_template() {
+        let path = output_path("out/{name}-{seed}.png", Path::new("test/Water.png"), 42);
+
+        assert_eq!(path, PathBuf::from("out/Water-42.png"));
+    }
+
+    #[test]
+    fn it_generates_every_sample_with_every_seed() {
+        let dir = std::env::temp_dir().join(format!("wfc-batch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let (b, w) = (Color(0xff000000), Color(0xffffffff));
+        let stripes = Image {
+            width: 5,
+            height: 1,
+            colors: vec![b, w, b, w, b],
+        };
+        save_image(stripes, dir.join("stripes.png"));
+        fs::write(dir.join("broken.png"), b"not a png").unwrap();
+
+        let batch = Batch {
+            samples: find_samples(&[dir.join("*.png").to_string_lossy().into_owned()]).unwrap(),
+            seeds: 0..3,
+            output: dir
+                .join("out/{name}-{seed}.png")
+                .to_string_lossy()
+                .into_owned(),
+            width: 9,
+            height: 1,
+            quantize: Quantize::default(),
+            indexed: false,
+        };
+        let summary = batch.run();
+
+        assert_eq!(summary.results.len(), 6);
+        assert_eq!(summary.successes, 3);
+        assert_eq!(summary.failures, 3);
+        assert!(dir.join("out/stripes-2.png").is_file());
+        assert!(!dir.join("out/broken-0.png").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn it_generates_16_bit_gray_from_its_values() {
+        let dir = std::env::temp_dir().join(format!("wfc-batch-16-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        // heights which only differ in their low byte
+        let (low, high) = (0x1234, 0x12ff);
+        let heightmap: ::image::ImageBuffer<::image::Luma<u16>, Vec<u16>> =
+            ::image::ImageBuffer::from_vec(5, 1, vec![low, high, low, high, low]).unwrap();
+        heightmap.save(dir.join("heights.png")).unwrap();
+
+        let mut batch = Batch {
+            samples: vec![dir.join("heights.png")],
+            seeds: 0..1,
+            output: dir.join("out.png").to_string_lossy().into_owned(),
+            width: 9,
+            height: 1,
+            quantize: Quantize::default(),
+            indexed: false,
+        };
+        let summary = batch.run();
+
+        assert_eq!(summary.successes, 1);
+        let out = ::image::open(dir.join("out.png")).unwrap().into_luma16();
+        assert!(
+            out.pixels()
+                .all(|pixel| pixel.0[0] == low || pixel.0[0] == high)
+        );
+        assert!(out.pixels().any(|pixel| pixel.0[0] == high));
+
+        batch.quantize.colors = Some(2);
+        assert_eq!(
+            batch.run().results[0].error.as_deref(),
+            Some("16 bit gray cannot be quantized")
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,61 @@
+use std::{fmt::Debug, hash::Hash};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+/// The value of a cell of the grid which the solver decides on, e.g. the `Color` of
+/// a pixel, the `char` of a text map, a tile index or a custom enum.
+///
+/// Cells are serializable so that rulesets and checkpoints can be saved.
+pub trait Cell: Copy + Eq + Hash + Debug + Send + Sync + Serialize + DeserializeOwned {}
+
+impl<C: Copy + Eq + Hash + Debug + Send + Sync + Serialize + DeserializeOwned> Cell for C {}
+
+#[cfg(test)]
+mod test {
+    use serde::Deserialize;
+
+    use super::*;
+
+    use crate::{
+        image::Image,
+        pattern8::Pattern8,
+        ruleset::Ruleset,
+        solver::{Solver, Status},
+        verify::verify,
+    };
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    enum Terrain {
+        Water,
+        Sand,
+        Grass,
+    }
+
+    #[test]
+    fn it_solves_custom_cells() {
+        use Terrain::*;
+        #[rustfmt::skip]
+        let colors = vec![
+            Water, Water, Sand, Grass, Grass, Sand,
+            Water, Water, Sand, Grass, Grass, Sand,
+            Sand, Sand, Sand, Sand, Sand, Sand,
+            Grass, Grass, Sand, Water, Water, Sand,
+            Grass, Grass, Sand, Water, Water, Sand,
+            Sand, Sand, Sand, Sand, Sand, Sand,
+        ];
+        let sample = Image {
+            width: 6,
+            height: 6,
+            colors,
+        };
+        let ruleset = Ruleset::<8, Pattern8<Terrain>>::extract(&sample);
+        let mut solver = Solver::<8, Pattern8<Terrain>>::from_sample(sample, 6, 6, 5);
+
+        let status = solver.run();
+        let output = solver.image_sp.to_image(Water, Water);
+
+        assert_eq!(status, Status::Solved);
+        assert!(output.colors.contains(&Grass));
+        assert_eq!(verify(&ruleset, &output, false), vec![]);
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+
+use crate::{
+    cell::Cell,
+    color::Color,
+    image::Image,
+    pattern8::Pattern8,
+    solver::{Solver, Status},
+    superposition::{ImageSuperposition, Wfc},
+    vec2::Vec2,
+};
+
+const MAX_ATTEMPTS: u64 = 10;
+
+/// The width of the margin around a chunk. The pattern of a pixel on the border of a
+/// chunk reaches one pixel further into the chunk, so the neighbor is fixed to two
+/// rows and columns for the border pixels to keep their patterns.
+const MARGIN: i32 = 2;
+
+/// The `MARGIN` outermost rows and columns of a generated chunk, which is everything
+/// a neighboring chunk needs to stitch seamlessly. Each side lists them from the edge
+/// inwards.
+#[derive(Clone, Debug)]
+pub struct ChunkBorder<C = Color> {
+    pub top: Vec<Vec<C>>,
+    pub bottom: Vec<Vec<C>>,
+    pub left: Vec<Vec<C>>,
+    pub right: Vec<Vec<C>>,
+}
+
+impl<C: Cell> ChunkBorder<C> {
+    fn from_image(image: &Image<C>) -> Self {
+        let (w, h) = (image.width as i32, image.height as i32);
+        let color_at = |x, y| image.get_color_at(Vec2 { x, y }).unwrap();
+        let row = |y| (0..w).map(|x| color_at(x, y)).collect();
+        let column = |x| (0..h).map(|y| color_at(x, y)).collect();
+
+        Self {
+            top: (0..MARGIN).map(row).collect(),
+            bottom: (0..MARGIN).map(|i| row(h - 1 - i)).collect(),
+            left: (0..MARGIN).map(column).collect(),
+            right: (0..MARGIN).map(|i| column(w - 1 - i)).collect(),
+        }
+    }
+
+    fn get_color_at(&self, pos: Vec2) -> Option<C> {
+        let (w, h) = (self.top[0].len() as i32, self.left[0].len() as i32);
+        let (x, y) = (pos.x as usize, pos.y as usize);
+        if pos.y < MARGIN {
+            self.top[y].get(x).copied()
+        } else if pos.y >= h - MARGIN {
+            self.bottom[(h - 1 - pos.y) as usize].get(x).copied()
+        } else if pos.x < MARGIN {
+            self.left[x].get(y).copied()
+        } else if pos.x >= w - MARGIN {
+            self.right[(w - 1 - pos.x) as usize].get(y).copied()
+        } else {
+            None
+        }
+    }
+}
+
+struct CachedChunk<C> {
+    border: ChunkBorder<C>,
+    constrained_by: Vec<(i32, i32)>,
+}
+
+/// Generates an unbounded world chunk by chunk. Every chunk is solved with a margin
+/// which is fixed to the borders of the already generated neighboring chunks.
+pub struct ChunkedGenerator<C = Color> {
+    sample: Image<C>,
+    world_seed: u64,
+    chunk_width: u32,
+    chunk_height: u32,
+    chunks: HashMap<(i32, i32), CachedChunk<C>>,
+}
+
+impl<C: Cell> ChunkedGenerator<C> {
+    pub fn new(sample: Image<C>, world_seed: u64, chunk_width: u32, chunk_height: u32) -> Self {
+        Self {
+            sample,
+            world_seed,
+            chunk_width,
+            chunk_height,
+            chunks: HashMap::new(),
+        }
+    }
+
+    pub fn chunk_seed(&self, cx: i32, cy: i32) -> u64 {
+        let coords = ((cx as u32 as u64) << 32) | cy as u32 as u64;
+        splitmix64(self.world_seed ^ splitmix64(coords))
+    }
+
+    pub fn get_border(&self, cx: i32, cy: i32) -> Option<&ChunkBorder<C>> {
+        self.chunks.get(&(cx, cy)).map(|chunk| &chunk.border)
+    }
+
+    /// Generates the chunk at (cx, cy). The result only depends on the world seed and
+    /// on the neighbors which were already generated when the chunk was first requested,
+    /// so requesting the same chunk again yields the same image.
+    /// Returns None if no solution was found.
+    pub fn generate(&mut self, cx: i32, cy: i32) -> Option<Image<C>> {
+        let constrained_by = match self.chunks.get(&(cx, cy)) {
+            Some(chunk) => chunk.constrained_by.clone(),
+            None => self.get_generated_neighbors(cx, cy),
+        };
+
+        let seed = self.chunk_seed(cx, cy);
+        let image = (0..MAX_ATTEMPTS).find_map(|attempt| {
+            self.solve_chunk(cx, cy, &constrained_by, seed.wrapping_add(attempt))
+        })?;
+
+        self.chunks.entry((cx, cy)).or_insert_with(|| CachedChunk {
+            border: ChunkBorder::from_image(&image),
+            constrained_by,
+        });
+
+        Some(image)
+    }
+
+    fn get_generated_neighbors(&self, cx: i32, cy: i32) -> Vec<(i32, i32)> {
+        let mut neighbors = Vec::new();
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let neighbor = (cx + dx, cy + dy);
+                if (dx, dy) != (0, 0) && self.chunks.contains_key(&neighbor) {
+                    neighbors.push(neighbor);
+                }
+            }
+        }
+        neighbors
+    }
+
+    fn solve_chunk(
+        &self,
+        cx: i32,
+        cy: i32,
+        constrained_by: &[(i32, i32)],
+        seed: u64,
+    ) -> Option<Image<C>> {
+        let (w, h) = (self.chunk_width as i32, self.chunk_height as i32);
+        let padded_width = self.chunk_width + 2 * MARGIN as u32;
+        let padded_height = self.chunk_height + 2 * MARGIN as u32;
+
+        let mut image_sp =
+            ImageSuperposition::<8, Pattern8<C>>::with_seed(padded_width, padded_height, seed);
+        image_sp.open_border = true;
+        image_sp.extract(self.sample.clone());
+
+        for i in 0..image_sp.pixels.len() {
+            let pos = Vec2::from_index(i, padded_width)
+                + Vec2 {
+                    x: -MARGIN,
+                    y: -MARGIN,
+                };
+            let (dx, dy) = (pos.x.div_euclid(w), pos.y.div_euclid(h));
+            if (dx, dy) == (0, 0) || !constrained_by.contains(&(cx + dx, cy + dy)) {
+                continue;
+            }
+
+            let neighbor_pos = Vec2 {
+                x: pos.x.rem_euclid(w),
+                y: pos.y.rem_euclid(h),
+            };
+            let color = self.chunks[&(cx + dx, cy + dy)]
+                .border
+                .get_color_at(neighbor_pos)
+                .expect("margin pixels lie on the border of the neighbor");
+            if !image_sp.fix_color_at(i, color) {
+                return None;
+            }
+        }
+
+        image_sp.propagate_all();
+
+        let mut solver = Solver::new(image_sp);
+        if solver.run() != Status::Solved {
+            return None;
+        }
+
+        // every pixel is collapsed, the margin is cropped
+        let mut colors = Vec::with_capacity((self.chunk_width * self.chunk_height) as usize);
+        for y in MARGIN..h + MARGIN {
+            for x in MARGIN..w + MARGIN {
+                let index = Vec2 { x, y }.into_index(padded_width);
+                colors.push(solver.image_sp.pixels[index].colors[0].color);
+            }
+        }
+
+        Some(Image {
+            width: self.chunk_width,
+            height: self.chunk_height,
+            colors,
+        })
+    }
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{image::load_image, ruleset::Ruleset, verify::verify};
+
+    fn checkerboard(size: u32) -> Image {
+        let colors = (0..size * size)
+            .map(|i| {
+                if (i % size + i / size).is_multiple_of(2) {
+                    Color(0xff000000)
+                } else {
+                    Color(0xffffffff)
+                }
+            })
+            .collect();
+
+        Image {
+            width: size,
+            height: size,
+            colors,
+        }
+    }
+
+    #[test]
+    fn it_derives_different_seeds_for_different_chunks() {
+        let generator = ChunkedGenerator::new(checkerboard(4), 42, 4, 4);
+
+        assert_eq!(generator.chunk_seed(1, 2), generator.chunk_seed(1, 2));
+        assert_ne!(generator.chunk_seed(1, 2), generator.chunk_seed(2, 1));
+        assert_ne!(generator.chunk_seed(0, 0), generator.chunk_seed(0, -1));
+    }
+
+    #[test]
+    fn it_generates_the_same_chunk_twice() {
+        let mut generator = ChunkedGenerator::new(checkerboard(4), 42, 5, 5);
+
+        let first = generator.generate(0, 0).unwrap();
+        generator.generate(1, 0).unwrap();
+        let second = generator.generate(0, 0).unwrap();
+
+        assert_eq!(first.colors, second.colors);
+    }
+
+    #[test]
+    fn it_stitches_valid_seams() {
+        let sample = load_image("./test/Water.png");
+        let ruleset = Ruleset::<8, Pattern8>::extract(&sample);
+        let mut generator = ChunkedGenerator::new(sample, 5, 8, 8);
+
+        let mut world = Image {
+            width: 16,
+            height: 16,
+            colors: vec![Color(0); 16 * 16],
+        };
+        for (cx, cy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let chunk = generator.generate(cx, cy).unwrap();
+            for (i, &color) in chunk.colors.iter().enumerate() {
+                let pos = Vec2::from_index(i, 8)
+                    + Vec2 {
+                        x: cx * 8,
+                        y: cy * 8,
+                    };
+                world.colors[pos.into_index(16)] = color;
+            }
+        }
+
+        // the outer border of the world is not the border of the sample
+        assert_eq!(verify(&ruleset, &world, true), vec![]);
+    }
+
+    #[test]
+    fn it_stitches_neighboring_chunks_seamlessly() {
+        let mut generator = ChunkedGenerator::new(checkerboard(4), 7, 5, 5);
+
+        let left = generator.generate(0, 0).unwrap();
+        let right = generator.generate(1, 0).unwrap();
+        let below = generator.generate(0, 1).unwrap();
+
+        for i in 0..5 {
+            assert_ne!(left.colors[i * 5 + 4], right.colors[i * 5]);
+            assert_ne!(left.colors[20 + i], below.colors[i]);
+        }
+    }
+}
@@ -1,7 +1,15 @@
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct Color(pub u32);
 
 impl Color {
+    pub const fn from_rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Color((r as u32) | (g as u32) << 8 | (b as u32) << 16 | (a as u32) << 24)
+    }
+
     pub fn r(&self) -> u8 {
         (self.0 & 0xFF) as u8
     }
@@ -18,3 +26,45 @@ impl Color {
         ((self.0 >> 24) & 0xFF) as u8
     }
 }
+
+/// Formats the color as "#rrggbbaa".
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "#{:02x}{:02x}{:02x}{:02x}",
+            self.r(),
+            self.g(),
+            self.b(),
+            self.a()
+        )
+    }
+}
+
+/// Colors are written as "#rrggbbaa" in human readable formats and as plain u32 otherwise.
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.to_string())
+        } else {
+            serializer.serialize_u32(self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            return u32::deserialize(deserializer).map(Color);
+        }
+
+        let hex = String::deserialize(deserializer)?;
+        let rgba = hex
+            .strip_prefix('#')
+            .filter(|digits| digits.len() == 8)
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| de::Error::custom(format!("invalid color '{}'", hex)))?;
+
+        Ok(Color(rgba.swap_bytes()))
+    }
+}
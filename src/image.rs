@@ -1,21 +1,26 @@
 use crate::color::Color;
 use crate::pixel::Pixel;
 use crate::vec2::Vec2;
-use ::image::GenericImageView;
-use ::image::open;
-use ::image::{ImageBuffer, Rgba};
+use ::image::ImageReader;
+use ::image::error::{EncodingError, ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
+use ::image::{ColorType, ImageBuffer, ImageError, ImageFormat, ImageResult, Rgba};
 use image::DynamicImage;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::BufWriter;
 use std::path::Path;
 
-#[derive(Clone)]
-pub struct Image {
+/// A grid of cells, the pixels of an image by default.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image<C = Color> {
     pub width: u32,
     pub height: u32,
-    pub colors: Vec<Color>,
+    pub colors: Vec<C>,
 }
 
-impl Image {
-    pub fn get_color_at(&self, pos: Vec2) -> Option<Color> {
+impl<C: Copy> Image<C> {
+    pub fn get_color_at(&self, pos: Vec2) -> Option<C> {
         if pos.x < 0 || pos.x >= self.width as i32 {
             return None;
         }
@@ -25,7 +30,9 @@ impl Image {
 
         Some(self.colors[(pos.y * self.width as i32 + pos.x) as usize])
     }
+}
 
+impl Image {
     pub fn set_pixel(&mut self, pixel: Pixel) {
         self.colors[(pixel.pos.y * self.width as i32 + pixel.pos.x) as usize] = pixel.color;
     }
@@ -33,35 +40,264 @@ impl Image {
 
 // TODO: make part of impl
 pub fn load_image<T: AsRef<Path>>(path: T) -> Image {
-    let img = open(path).unwrap().into_rgba8();
-    let img = DynamicImage::ImageRgba8(img);
+    try_load_image(path).unwrap()
+}
 
-    let (width, height) = img.dimensions();
-    let bytes = img.into_bytes();
+pub fn try_load_image<T: AsRef<Path>>(path: T) -> ImageResult<Image> {
+    try_load_image_with_format(path).map(|(image, _)| image)
+}
 
-    let mut colors = vec![Color(0); (width * height) as usize];
+/// How the pixels of an image file are stored, so that the output can be written
+/// like the sample.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PixelFormat {
+    L8,
+    La8,
+    Rgb8,
+    #[default]
+    Rgba8,
+    /// 16 bit gray, e.g. for heightmaps, which `try_load_sample` keeps lossless.
+    L16,
+    /// Colors only have 8 bit channels, so 16 bit channels are reduced to 8 bit on
+    /// load and saved with that precision.
+    La16,
+    Rgb16,
+    Rgba16,
+    /// A png with a palette of up to 256 colors.
+    Indexed,
+}
 
-    for y in 0..height {
-        for x in 0..width {
-            let i = (width * y + x) as usize;
-            let rgba = (bytes[4 * i] as u32)
-                | (bytes[4 * i + 1] as u32) << 8
-                | (bytes[4 * i + 2] as u32) << 16
-                | (bytes[4 * i + 3] as u32) << 24;
+/// A sample as stored in its file. 16 bit gray, e.g. a heightmap, keeps its values
+/// as cells, all other pixel formats are loaded as colors.
+pub enum Sample {
+    Colors(Image, PixelFormat),
+    Gray16(Image<u16>),
+}
 
-            colors[i] = Color(rgba);
-        }
+/// Loads the image and the format of its pixels.
+pub fn try_load_image_with_format<T: AsRef<Path>>(path: T) -> ImageResult<(Image, PixelFormat)> {
+    let (dynamic, format) = decode(&path)?;
+    Ok((to_colors(&path, dynamic, format), format))
+}
+
+/// Loads 16 bit gray losslessly and any other image with its pixel format.
+pub fn try_load_sample<T: AsRef<Path>>(path: T) -> ImageResult<Sample> {
+    match decode(&path)? {
+        (DynamicImage::ImageLuma16(buffer), _) => Ok(Sample::Gray16(Image {
+            width: buffer.width(),
+            height: buffer.height(),
+            colors: buffer.into_raw(),
+        })),
+        (dynamic, format) => Ok(Sample::Colors(to_colors(&path, dynamic, format), format)),
+    }
+}
+
+fn decode<T: AsRef<Path>>(path: T) -> ImageResult<(DynamicImage, PixelFormat)> {
+    let reader = ImageReader::open(&path)?.with_guessed_format()?;
+    let is_png = reader.format() == Some(ImageFormat::Png);
+    let dynamic = reader.decode()?;
+
+    let format = match &dynamic {
+        _ if is_png && is_indexed_png(&path)? => PixelFormat::Indexed,
+        DynamicImage::ImageLuma8(_) => PixelFormat::L8,
+        DynamicImage::ImageLumaA8(_) => PixelFormat::La8,
+        DynamicImage::ImageRgb8(_) | DynamicImage::ImageRgb32F(_) => PixelFormat::Rgb8,
+        DynamicImage::ImageLuma16(_) => PixelFormat::L16,
+        DynamicImage::ImageLumaA16(_) => PixelFormat::La16,
+        DynamicImage::ImageRgb16(_) => PixelFormat::Rgb16,
+        DynamicImage::ImageRgba16(_) => PixelFormat::Rgba16,
+        _ => PixelFormat::Rgba8,
+    };
+    Ok((dynamic, format))
+}
+
+fn to_colors<T: AsRef<Path>>(path: T, dynamic: DynamicImage, format: PixelFormat) -> Image {
+    if matches!(
+        format,
+        PixelFormat::L16 | PixelFormat::La16 | PixelFormat::Rgb16 | PixelFormat::Rgba16
+    ) {
+        log::warn!(
+            "{}: 16 bit channels are reduced to 8 bit",
+            path.as_ref().display()
+        );
     }
 
     Image {
-        width,
-        height,
-        colors,
+        width: dynamic.width(),
+        height: dynamic.height(),
+        colors: dynamic
+            .into_rgba8()
+            .pixels()
+            .map(|&Rgba([r, g, b, a])| Color::from_rgba(r, g, b, a))
+            .collect(),
+    }
+}
+
+fn is_indexed_png<T: AsRef<Path>>(path: T) -> ImageResult<bool> {
+    let decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    let reader = decoder
+        .read_info()
+        .map_err(|err| ImageError::IoError(std::io::Error::other(err)))?;
+    Ok(reader.info().color_type == png::ColorType::Indexed)
+}
+
+/// Saves the image in the file format of the extension and with the given pixel
+/// format, falling back to 8 bit RGBA and then RGB if the file format does not
+/// support it. 16 bit gray falls back to 8 bit gray first.
+pub fn try_save_image<T: AsRef<Path>>(
+    image: &Image,
+    path: T,
+    format: PixelFormat,
+) -> ImageResult<()> {
+    let path = path.as_ref();
+    let file_format = ImageFormat::from_path(path)?;
+
+    let fallbacks: &[PixelFormat] = match format {
+        PixelFormat::L16 => &[PixelFormat::L8, PixelFormat::Rgba8, PixelFormat::Rgb8],
+        _ => &[PixelFormat::Rgba8, PixelFormat::Rgb8],
+    };
+    let mut result = encode(image, path, file_format, format);
+    for &fallback in fallbacks {
+        let Err(ImageError::Unsupported(err)) = &result else {
+            break;
+        };
+        if fallback != format {
+            log::debug!("{}: {}, trying {:?}", path.display(), err, fallback);
+            result = encode(image, path, file_format, fallback);
+        }
     }
+    result
+}
+
+/// Saves 16 bit gray losslessly, or the high bytes as 8 bit gray if the file format
+/// does not support 16 bit gray.
+pub fn try_save_gray16<T: AsRef<Path>>(image: &Image<u16>, path: T) -> ImageResult<()> {
+    let path = path.as_ref();
+    let file_format = ImageFormat::from_path(path)?;
+    let buffer = ImageBuffer::from_raw(image.width, image.height, image.colors.clone())
+        .expect("image has a value for every pixel");
+
+    match save_with_format(&DynamicImage::ImageLuma16(buffer), path, file_format) {
+        Err(ImageError::Unsupported(err)) => {
+            log::debug!("{}: {}, trying {:?}", path.display(), err, PixelFormat::L8);
+            let gray = Image {
+                width: image.width,
+                height: image.height,
+                colors: image
+                    .colors
+                    .iter()
+                    .map(|value| {
+                        let [high, _] = value.to_be_bytes();
+                        Color::from_rgba(high, high, high, 0xff)
+                    })
+                    .collect(),
+            };
+            try_save_image(&gray, path, PixelFormat::L8)
+        }
+        result => result,
+    }
+}
+
+fn encode(
+    image: &Image,
+    path: &Path,
+    file_format: ImageFormat,
+    format: PixelFormat,
+) -> ImageResult<()> {
+    let rgba = DynamicImage::ImageRgba8(to_rgba_image(image));
+    let dynamic = match format {
+        PixelFormat::L8 => DynamicImage::ImageLuma8(rgba.to_luma8()),
+        PixelFormat::La8 => DynamicImage::ImageLumaA8(rgba.to_luma_alpha8()),
+        PixelFormat::Rgb8 => DynamicImage::ImageRgb8(rgba.to_rgb8()),
+        PixelFormat::Rgba8 => rgba,
+        PixelFormat::L16 => DynamicImage::ImageLuma16(rgba.to_luma16()),
+        PixelFormat::La16 => DynamicImage::ImageLumaA16(rgba.to_luma_alpha16()),
+        PixelFormat::Rgb16 => DynamicImage::ImageRgb16(rgba.to_rgb16()),
+        PixelFormat::Rgba16 => DynamicImage::ImageRgba16(rgba.to_rgba16()),
+        PixelFormat::Indexed if file_format == ImageFormat::Png => {
+            return save_indexed_png(image, path);
+        }
+        PixelFormat::Indexed => rgba,
+    };
+
+    save_with_format(&dynamic, path, file_format)
+}
+
+fn save_with_format(
+    dynamic: &DynamicImage,
+    path: &Path,
+    file_format: ImageFormat,
+) -> ImageResult<()> {
+    // the bmp encoder reports unsupported colors as io errors
+    let color = dynamic.color();
+    if file_format == ImageFormat::Bmp
+        && !matches!(
+            color,
+            ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8
+        )
+    {
+        return Err(ImageError::Unsupported(
+            UnsupportedError::from_format_and_kind(
+                ImageFormatHint::Exact(file_format),
+                UnsupportedErrorKind::Color(color.into()),
+            ),
+        ));
+    }
+    dynamic.save_with_format(path, file_format)
+}
+
+/// Writes a png with a palette, or fails as unsupported if there are too many colors.
+fn save_indexed_png(image: &Image, path: &Path) -> ImageResult<()> {
+    let mut palette: Vec<Color> = Vec::new();
+    let mut color_indices: HashMap<Color, u8> = HashMap::new();
+    let mut indices = Vec::with_capacity(image.colors.len());
+    for &color in &image.colors {
+        let index = match color_indices.get(&color) {
+            Some(&index) => index,
+            None => {
+                let index = u8::try_from(palette.len()).map_err(|_| {
+                    ImageError::Unsupported(ImageFormatHint::Exact(ImageFormat::Png).into())
+                })?;
+                color_indices.insert(color, index);
+                palette.push(color);
+                index
+            }
+        };
+        indices.push(index);
+    }
+
+    let to_image_error = |err: png::EncodingError| {
+        ImageError::Encoding(EncodingError::new(
+            ImageFormatHint::Exact(ImageFormat::Png),
+            err,
+        ))
+    };
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, image.width, image.height);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(
+        palette
+            .iter()
+            .flat_map(|color| [color.r(), color.g(), color.b()])
+            .collect::<Vec<u8>>(),
+    );
+    if palette.iter().any(|color| color.a() != 0xff) {
+        encoder.set_trns(palette.iter().map(|color| color.a()).collect::<Vec<u8>>());
+    }
+
+    let mut writer = encoder.write_header().map_err(to_image_error)?;
+    writer.write_image_data(&indices).map_err(to_image_error)
 }
 
 // TODO: make part of impl
 pub fn save_image<T: AsRef<Path>>(image: Image, path: T) {
+    to_rgba_image(&image)
+        .save(path)
+        .expect("Failed to save image");
+}
+
+pub fn to_rgba_image(image: &Image) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
     let mut img_buffer: ImageBuffer<Rgba<u8>, Vec<u8>> =
         ImageBuffer::new(image.width, image.height);
 
@@ -73,7 +309,7 @@ pub fn save_image<T: AsRef<Path>>(image: Image, path: T) {
         }
     }
 
-    img_buffer.save(path).expect("Failed to save image");
+    img_buffer
 }
 
 #[cfg(test)]
@@ -81,6 +317,7 @@ mod test {
 
     use super::*;
     use crate::vec2::Vec2;
+    use ::image::Luma;
 
     #[test]
     fn it_should_read_and_then_write_a_red_line_to_an_image() {
@@ -94,6 +331,85 @@ mod test {
             });
         }
 
-        save_image(image, "./test/flowers_test.png");
+        let path = std::env::temp_dir().join(format!("wfc-image-{}.png", std::process::id()));
+        save_image(image, &path);
+
+        let image = load_image(&path);
+        for x in 3..10 {
+            assert_eq!(image.get_color_at(Vec2 { x, y }), Some(Color(0xFF0000FF)));
+        }
+        assert_ne!(
+            image.get_color_at(Vec2 { x: 10, y }),
+            Some(Color(0xFF0000FF))
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn it_keeps_16_bit_gray_lossless() {
+        let dir = std::env::temp_dir().join(format!("wfc-image-16-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let heightmap: ImageBuffer<Luma<u16>, Vec<u16>> =
+            ImageBuffer::from_vec(4, 1, vec![0, 1, 256, 65535]).unwrap();
+        heightmap.save(dir.join("heightmap.png")).unwrap();
+
+        let Sample::Gray16(image) = try_load_sample(dir.join("heightmap.png")).unwrap() else {
+            panic!("16 bit gray is loaded as colors");
+        };
+        try_save_gray16(&image, dir.join("out.png")).unwrap();
+        try_save_gray16(&image, dir.join("out.bmp")).unwrap();
+
+        assert_eq!(image.colors, vec![0, 1, 256, 65535]);
+        let out = ::image::open(dir.join("out.png")).unwrap();
+        assert_eq!(out.as_luma16(), Some(&heightmap));
+        // bmp has no 16 bit gray, so it keeps the high byte as gray
+        let gray = |values: [u8; 4]| values.map(|v| Color::from_rgba(v, v, v, 0xff)).to_vec();
+        assert_eq!(load_image(dir.join("out.bmp")).colors, gray([0, 0, 1, 255]));
+        // colors only have 8 bits per channel
+        let (colors, format) = try_load_image_with_format(dir.join("heightmap.png")).unwrap();
+        assert_eq!(format, PixelFormat::L16);
+        assert_eq!(colors.colors, gray([0, 0, 1, 255]));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn it_writes_the_pixel_format_of_the_sample() {
+        let dir = std::env::temp_dir().join(format!("wfc-image-formats-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let gray = Image {
+            width: 3,
+            height: 1,
+            colors: vec![
+                Color::from_rgba(0, 0, 0, 0xff),
+                Color::from_rgba(0x80, 0x80, 0x80, 0xff),
+                Color::from_rgba(0xff, 0xff, 0xff, 0xff),
+            ],
+        };
+        let colored = Image {
+            width: 3,
+            height: 1,
+            colors: vec![
+                Color::from_rgba(0xff, 0, 0, 0xff),
+                Color::from_rgba(0, 0xff, 0, 0x80),
+                Color::from_rgba(0, 0, 0xff, 0),
+            ],
+        };
+
+        let cases = [
+            (&gray, "l8.png", PixelFormat::L8),
+            (&colored, "indexed.png", PixelFormat::Indexed),
+            (&colored, "rgba.bmp", PixelFormat::Rgba8),
+            (&colored, "rgba.tga", PixelFormat::Rgba8),
+            (&colored, "rgba.webp", PixelFormat::Rgba8),
+            (&gray, "rgb.gif", PixelFormat::Rgba8),
+        ];
+        for (image, name, format) in cases {
+            try_save_image(image, dir.join(name), format).unwrap();
+
+            let (loaded, loaded_format) = try_load_image_with_format(dir.join(name)).unwrap();
+            assert_eq!(&loaded, image, "{}", name);
+            assert_eq!(loaded_format, format, "{}", name);
+        }
+        std::fs::remove_dir_all(dir).unwrap();
     }
 }
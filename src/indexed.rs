@@ -0,0 +1,184 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    color::Color,
+    image::Image,
+    pattern::Pattern,
+    superposition::{CONTRADICTION, ImageSuperposition, UNDECIDED},
+};
+
+#[derive(Debug)]
+pub enum IndexedError {
+    TooManyColors(usize),
+}
+
+impl fmt::Display for IndexedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IndexedError::TooManyColors(count) => write!(
+                f,
+                "{} colors do not fit into 16 bit indices, quantize the sample first",
+                count
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IndexedError {}
+
+/// A sample with every color replaced by its index into the palette.
+///
+/// The solver runs on the index map, so colors are only looked up again when the
+/// result is rendered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexedImage {
+    pub width: u32,
+    pub height: u32,
+    pub indices: Vec<u16>,
+    /// The distinct colors in the order they first occur in the sample.
+    pub palette: Vec<Color>,
+}
+
+impl IndexedImage {
+    pub fn from_image(image: &Image) -> Result<Self, IndexedError> {
+        let mut color_indices: HashMap<Color, u16> = HashMap::new();
+        let mut palette = Vec::new();
+        let mut indices = Vec::with_capacity(image.colors.len());
+
+        for &color in &image.colors {
+            let index = match color_indices.get(&color) {
+                Some(&index) => index,
+                None => {
+                    let index = u16::try_from(palette.len())
+                        .map_err(|_| IndexedError::TooManyColors(count_colors(image)))?;
+                    color_indices.insert(color, index);
+                    palette.push(color);
+                    index
+                }
+            };
+            indices.push(index);
+        }
+
+        Ok(Self {
+            width: image.width,
+            height: image.height,
+            indices,
+            palette,
+        })
+    }
+
+    /// The image with the palette colors.
+    pub fn to_image(&self) -> Image {
+        Image {
+            width: self.width,
+            height: self.height,
+            colors: self
+                .indices
+                .iter()
+                .map(|&index| self.palette[index as usize])
+                .collect(),
+        }
+    }
+
+    /// The index map the solver runs on.
+    pub fn to_index_map(&self) -> Image<u16> {
+        Image {
+            width: self.width,
+            height: self.height,
+            colors: self.indices.clone(),
+        }
+    }
+
+    /// Looks up the palette color of every collapsed pixel, undecided pixels and
+    /// contradictions are painted like `Image::from`.
+    pub fn render<const N: usize, T: Pattern<N, Value = u16>>(
+        &self,
+        image_sp: &ImageSuperposition<N, T>,
+    ) -> Image {
+        let colors = image_sp
+            .pixels
+            .iter()
+            .map(|pixel_sp| match pixel_sp.colors.len() {
+                0 => CONTRADICTION,
+                1 => self.palette[pixel_sp.colors[0].color as usize],
+                _ => UNDECIDED,
+            })
+            .collect();
+
+        Image {
+            width: image_sp.width,
+            height: image_sp.height,
+            colors,
+        }
+    }
+}
+
+fn count_colors(image: &Image) -> usize {
+    let mut colors = image.colors.clone();
+    colors.sort_by_key(|color| color.0);
+    colors.dedup();
+    colors.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        image::load_image,
+        pattern8::Pattern8,
+        solver::{Solver, Status},
+    };
+
+    #[test]
+    fn it_round_trips_the_colors() {
+        let sample = load_image("./test/flowers.png");
+
+        let indexed = IndexedImage::from_image(&sample).unwrap();
+
+        assert_eq!(indexed.to_image(), sample);
+        assert!(indexed.palette.len() < 16);
+        assert!(
+            indexed
+                .to_index_map()
+                .colors
+                .iter()
+                .all(|&index| index < 16)
+        );
+    }
+
+    #[test]
+    fn it_solves_the_same_on_indices_as_on_colors() {
+        let sample = load_image("./test/Water.png");
+        let indexed = IndexedImage::from_image(&sample).unwrap();
+
+        let mut on_colors = Solver::<8, Pattern8>::from_sample(sample, 12, 12, 7);
+        let mut on_indices =
+            Solver::<8, Pattern8<u16>>::from_sample(indexed.to_index_map(), 12, 12, 7);
+
+        assert_eq!(on_colors.run(), Status::Solved);
+        assert_eq!(on_indices.run(), Status::Solved);
+        assert_eq!(
+            indexed.render(&on_indices.image_sp),
+            Image::from(&on_colors.image_sp)
+        );
+    }
+
+    #[test]
+    fn it_stores_smaller_patterns_than_colors() {
+        assert!(std::mem::size_of::<Pattern8<u16>>() < std::mem::size_of::<Pattern8>());
+    }
+
+    #[test]
+    fn it_rejects_more_colors_than_indices() {
+        let image = Image {
+            width: 1 << 17,
+            height: 1,
+            colors: (0..1 << 17).map(Color).collect(),
+        };
+
+        assert!(matches!(
+            IndexedImage::from_image(&image),
+            Err(IndexedError::TooManyColors(131072))
+        ));
+    }
+}
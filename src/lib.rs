@@ -0,0 +1,33 @@
+pub mod batch;
+pub mod cell;
+pub mod chunk;
+pub mod color;
+pub mod image;
+pub mod indexed;
+pub mod observer;
+pub mod pattern;
+pub mod pattern26;
+pub mod pattern6;
+pub mod pattern8;
+pub mod pixel;
+pub mod quantize;
+pub mod race;
+pub mod recorder;
+pub mod render;
+pub mod ruleset;
+pub mod snapshot;
+pub mod solver;
+pub mod stack_set;
+pub mod stats;
+pub mod superposition;
+pub mod text;
+pub mod tile_inference;
+pub mod tile_pattern;
+pub mod tile_set;
+pub mod tiled;
+pub mod vec2;
+pub mod vec3;
+pub mod verify;
+pub mod vox;
+pub mod voxel;
+pub mod weighted;
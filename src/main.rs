@@ -1,74 +1,597 @@
-use image::Image;
-use image::load_image;
-use image::save_image;
-use pattern8::Pattern8;
-use snapshot::Snapshot;
-use snapshot::SnapshotStack;
-use superposition::ImageSuperposition;
-use superposition::Wfc;
-
-pub mod color;
-pub mod image;
-pub mod pattern;
-pub mod pattern8;
-pub mod pixel;
-pub mod snapshot;
-pub mod stack_set;
-pub mod superposition;
-pub mod vec2;
-pub mod weighted;
-
-fn main() {
-    println!("Hello, world!");
-
-    let mut snapshot_stack = SnapshotStack::<8, Pattern8>::new();
-    let image = load_image("./test/Water.png");
-
-    let mut image_sp = ImageSuperposition::<8, Pattern8>::new(50, 50);
-    image_sp.extract(image);
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
 
-    image_sp.propagate_all();
-    save_image(
-        Image::from(&image_sp),
-        format!("./test/out-before-collapse.png"),
+use clap::builder::RangedU64ValueParser;
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use log::LevelFilter;
+use wfc::batch::{Batch, find_samples, parse_seed_range};
+use wfc::cell::Cell;
+use wfc::color::Color;
+use wfc::image::Image;
+use wfc::image::save_image;
+use wfc::image::try_load_image;
+use wfc::image::try_load_image_with_format;
+use wfc::image::try_save_image;
+use wfc::indexed::IndexedImage;
+use wfc::observer::LogObserver;
+use wfc::pattern::VoxelPattern;
+use wfc::pattern6::Pattern6;
+use wfc::pattern8::Pattern8;
+use wfc::pattern26::Pattern26;
+use wfc::quantize::Method;
+use wfc::quantize::Quantize;
+use wfc::race::race;
+use wfc::recorder::Recorder;
+use wfc::render::RenderMode;
+use wfc::render::render;
+use wfc::ruleset::Ruleset;
+use wfc::solver::Solver;
+use wfc::solver::Status;
+use wfc::superposition::time_seed;
+use wfc::superposition::{ImageSuperposition, Wfc};
+use wfc::text::{load_text, render_text, save_text, to_text};
+use wfc::verify::verify;
+use wfc::vox::{check_size, load_vox, save_vox};
+use wfc::voxel::{EMPTY, VoxelGrid, load_slices, save_slices};
+
+/// Generates a texture from the sample with the overlapping model.
+#[derive(Parser)]
+struct Cli {
+    /// Log more details, repeat for even more (-vv logs every collapse).
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+
+    /// Log errors only.
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Print the statistics of the run as json to stdout.
+    #[arg(long)]
+    stats: bool,
+
+    /// Record the progress as animated gif, or as png sequence into the directory if
+    /// the path does not end in .gif.
+    #[arg(long, value_name = "PATH")]
+    record: Option<PathBuf>,
+
+    /// Race this many solvers with consecutive seeds on as many threads and keep the
+    /// first solution. No animation is recorded.
+    #[arg(long, value_name = "K", value_parser = RangedU64ValueParser::<u64>::new().range(1..))]
+    race: Option<u64>,
+
+    /// Solve on the palette indices of the sample instead of its colors. No animation
+    /// is recorded.
+    #[arg(long)]
+    indexed: bool,
+
+    #[command(flatten)]
+    quantize: QuantizeArgs,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generates an image for every combination of sample and seed in parallel.
+    Batch(BatchArgs),
+    /// Checks that every pixel's neighborhood in the output occurs in the sample.
+    Verify(VerifyArgs),
+    /// Generates a text map like ASCII level data, treating every char as a color.
+    Text(TextArgs),
+    /// Generates a voxel model from a MagicaVoxel model or a stack of png slices.
+    Voxel(VoxelArgs),
+}
+
+#[derive(Args)]
+struct VoxelArgs {
+    /// A MagicaVoxel .vox file, or png slices or glob patterns like 'slices/*.png'.
+    /// Slices are sorted by name, the lowest layer first.
+    #[arg(required = true)]
+    samples: Vec<String>,
+
+    /// A .vox file, or png slices with {z} replaced by the layer like 'out-{z}.png'.
+    #[arg(short, long, default_value = "out.vox")]
+    output: String,
+
+    #[arg(long, default_value_t = 32, value_parser = RangedU64ValueParser::<u32>::new().range(1..))]
+    width: u32,
+
+    #[arg(long, default_value_t = 32, value_parser = RangedU64ValueParser::<u32>::new().range(1..))]
+    height: u32,
+
+    /// The number of layers, the depth of the sample by default.
+    #[arg(long, value_parser = RangedU64ValueParser::<u32>::new().range(1..))]
+    depth: Option<u32>,
+
+    /// The current time by default.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// The voxels around every voxel which have to match the sample: the 6 sharing
+    /// a face, or all 26 which needs a larger sample.
+    #[arg(long, value_enum, default_value_t = Neighborhood::Faces)]
+    neighborhood: Neighborhood,
+
+    /// Allow any voxel outside of the model instead of the border of the sample.
+    #[arg(long)]
+    open_border: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Neighborhood {
+    #[value(name = "6")]
+    Faces,
+    #[value(name = "26")]
+    All,
+}
+
+#[derive(Args)]
+struct TextArgs {
+    /// The sample, with the same number of chars on every line.
+    sample: PathBuf,
+
+    /// Write the map to this file instead of stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    #[arg(long, default_value_t = 40, value_parser = RangedU64ValueParser::<u32>::new().range(1..))]
+    width: u32,
+
+    #[arg(long, default_value_t = 20, value_parser = RangedU64ValueParser::<u32>::new().range(1..))]
+    height: u32,
+
+    /// The current time by default.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Allow any char outside of the map instead of the border of the sample.
+    #[arg(long)]
+    open_border: bool,
+}
+
+#[derive(Args)]
+struct VerifyArgs {
+    /// The sample image, or a ruleset saved from it.
+    sample: PathBuf,
+
+    /// The generated image.
+    output: PathBuf,
+
+    /// Allow any color outside of the output, e.g. for chunks of a larger world.
+    #[arg(long)]
+    open_border: bool,
+
+    #[command(flatten)]
+    quantize: QuantizeArgs,
+}
+
+#[derive(Args)]
+struct BatchArgs {
+    /// Sample images or glob patterns like 'test/*.png'.
+    #[arg(required = true)]
+    samples: Vec<String>,
+
+    /// A single seed, a range like 0..10 or an inclusive range like 0..=9.
+    #[arg(long, default_value = "0..10")]
+    seeds: String,
+
+    /// Output path, {name} is replaced by the sample name and {seed} by the seed.
+    #[arg(short, long, default_value = "{name}-{seed}.png")]
+    output: String,
+
+    #[arg(long, default_value_t = 50, value_parser = RangedU64ValueParser::<u32>::new().range(1..))]
+    width: u32,
+
+    #[arg(long, default_value_t = 50, value_parser = RangedU64ValueParser::<u32>::new().range(1..))]
+    height: u32,
+
+    /// Number of threads, all cores by default.
+    #[arg(short, long)]
+    jobs: Option<usize>,
+
+    /// Write the results and timings of every run as json to this file.
+    #[arg(long)]
+    summary: Option<PathBuf>,
+
+    /// Solve on the palette indices of the samples instead of their colors.
+    #[arg(long)]
+    indexed: bool,
+
+    #[command(flatten)]
+    quantize: QuantizeArgs,
+}
+
+#[derive(Args)]
+struct QuantizeArgs {
+    /// Reduce the sample to at most this many colors, e.g. for photos or
+    /// anti-aliased samples.
+    #[arg(long, value_name = "K", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    colors: Option<usize>,
+
+    /// How to pick the colors when reducing them with --colors.
+    #[arg(long, value_enum, default_value_t = QuantizeMethod::MedianCut)]
+    quantizer: QuantizeMethod,
+
+    /// Snap colors whose channels differ by at most this much to the more frequent
+    /// one before reducing them.
+    #[arg(long, default_value_t = 0)]
+    tolerance: u8,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum QuantizeMethod {
+    MedianCut,
+    KMeans,
+}
+
+impl QuantizeArgs {
+    fn to_quantize(&self) -> Quantize {
+        Quantize {
+            method: match self.quantizer {
+                QuantizeMethod::MedianCut => Method::MedianCut,
+                QuantizeMethod::KMeans => Method::KMeans,
+            },
+            colors: self.colors,
+            tolerance: self.tolerance,
+        }
+    }
+
+    /// Quantizes the sample if enabled and reports the resulting palette.
+    fn apply(&self, sample: Image) -> Image {
+        let quantize = self.to_quantize();
+        if !quantize.is_enabled() {
+            return sample;
+        }
+
+        let (sample, palette) = quantize.apply(sample);
+        let colors: Vec<String> = palette
+            .colors
+            .iter()
+            .map(|color| color.to_string())
+            .collect();
+        log::info!("palette of {} colors: {}", colors.len(), colors.join(" "));
+        sample
+    }
+}
+
+impl Cli {
+    fn log_level(&self) -> LevelFilter {
+        if self.quiet {
+            return LevelFilter::Error;
+        }
+
+        match self.verbose {
+            0 => LevelFilter::Info,
+            1 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    env_logger::Builder::new()
+        .filter_level(cli.log_level())
+        .parse_default_env()
+        .init();
+
+    match &cli.command {
+        Some(Command::Batch(args)) => run_batch(args),
+        Some(Command::Verify(args)) => run_verify(args),
+        Some(Command::Text(args)) => run_text(args),
+        Some(Command::Voxel(args)) => run_voxel(args),
+        None => {
+            generate(&cli);
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+fn generate(cli: &Cli) {
+    let (image, format) =
+        try_load_image_with_format("./test/Water.png").expect("Failed to load sample");
+    let image = cli.quantize.apply(image);
+
+    let seed = time_seed();
+    log::info!("seed: {}", seed);
+    let (image_out, stats) = if cli.indexed {
+        let indexed = IndexedImage::from_image(&image).expect("Failed to index sample");
+        let mut solver =
+            Solver::<8, Pattern8<u16>>::from_sample(indexed.to_index_map(), 50, 50, seed);
+        if !try_race(cli, &mut solver, seed) {
+            solver.run_with(&mut LogObserver);
+        }
+        (indexed.render(&solver.image_sp), solver.stats)
+    } else {
+        let mut solver = Solver::<8, Pattern8>::from_sample(image, 50, 50, seed);
+        save_image(
+            render(&solver.image_sp, RenderMode::Average),
+            "./test/out-before-collapse.png",
+        );
+        if !try_race(cli, &mut solver, seed) {
+            match &cli.record {
+                Some(path) => run_recorded(&mut solver, path),
+                None => {
+                    solver.run_with(&mut LogObserver);
+                }
+            }
+        }
+        (Image::from(&solver.image_sp), solver.stats)
+    };
+
+    try_save_image(&image_out, "./test/out.png", format).expect("Failed to save image");
+
+    if cli.stats {
+        let json = serde_json::to_string_pretty(&stats).expect("stats are serializable");
+        println!("{}", json);
+    }
+}
+
+/// Runs the solver and saves every 10th step as animated gif, or as png sequence if
+/// the path does not end in .gif.
+fn run_recorded(solver: &mut Solver<8, Pattern8>, path: &Path) {
+    let mut recorder = Recorder::new(10, RenderMode::Average);
+    solver.run_with(&mut (&mut recorder, LogObserver));
+    recorder.capture(&solver.image_sp);
+
+    if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"))
+    {
+        recorder
+            .save_gif(path, 40)
+            .expect("Failed to save animation");
+    } else {
+        recorder
+            .save_png_sequence(path)
+            .expect("Failed to save animation");
+    }
+}
+
+/// Races as many solvers as requested with `--race` and keeps the winner. Returns
+/// false if racing was not requested.
+fn try_race<C: Cell>(cli: &Cli, solver: &mut Solver<8, Pattern8<C>>, seed: u64) -> bool {
+    let Some(attempts) = cli.race else {
+        return false;
+    };
+
+    let seeds: Vec<u64> = (0..attempts).map(|i| seed.wrapping_add(i)).collect();
+    match race(&solver.image_sp, &seeds) {
+        Some(mut winner) => {
+            winner.stats.timings.extract = solver.stats.timings.extract;
+            winner.stats.timings.propagate = solver.stats.timings.propagate;
+            *solver = winner;
+        }
+        None => log::error!("all {} seeds ran into a contradiction", attempts),
+    }
+    true
+}
+
+fn run_batch(args: &BatchArgs) -> ExitCode {
+    let (samples, seeds) = match (find_samples(&args.samples), parse_seed_range(&args.seeds)) {
+        (Ok(samples), Ok(seeds)) => (samples, seeds),
+        (Err(err), _) | (_, Err(err)) => {
+            log::error!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Some(jobs) = args.jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()
+            .expect("thread pool is only built once");
+    }
+
+    let batch = Batch {
+        samples,
+        seeds,
+        output: args.output.clone(),
+        width: args.width,
+        height: args.height,
+        quantize: args.quantize.to_quantize(),
+        indexed: args.indexed,
+    };
+    let summary = batch.run();
+
+    for result in &summary.results {
+        if let Some(error) = &result.error {
+            println!(
+                "failed: {} with seed {}: {}",
+                result.sample.display(),
+                result.seed,
+                error
+            );
+        }
+    }
+    println!(
+        "{} succeeded, {} failed in {:.2}s ({:.2}s per run)",
+        summary.successes,
+        summary.failures,
+        summary.seconds,
+        summary
+            .results
+            .iter()
+            .map(|result| result.seconds)
+            .sum::<f64>()
+            / summary.results.len() as f64
     );
 
-    // TODO: debug
-    //let mut n = 0;
-    while let Some(pixel_index) = image_sp.search() {
-        //save_image(Image::from(&image_sp), format!("./test/out{}.png", n));
-        //n += 1;
-
-        let image_sp_clone = image_sp.clone();
-
-        let collapse_color_index = image_sp.collapse(pixel_index);
-
-        snapshot_stack.push(Snapshot {
-            image_sp: image_sp_clone,
-            collapse_pixel_index: pixel_index,
-            collapse_color_index,
-        });
-
-        //let is_contradiction = !image_sp.propagate(pixel_index);
-        //if is_contradiction {
-        //    // TODO: Option needed?
-        //    println!("restore");
-        //    let snapshot = snapshot_stack.pop().unwrap();
-        //    image_sp = snapshot.image_sp;
-        //    image_sp.propagate(snapshot.collapse_pixel_index);
-        //}
-
-
-        let mut pi = pixel_index;
-        while !image_sp.propagate(pi) {
-            // TODO: Option needed?
-            println!("restore, stack size: {}", snapshot_stack.len());
-            let snapshot = snapshot_stack.pop().unwrap();
-            image_sp = snapshot.image_sp;
-            pi = snapshot.collapse_pixel_index;
+    if let Some(path) = &args.summary {
+        let json = serde_json::to_string_pretty(&summary).expect("summary is serializable");
+        if let Err(err) = fs::write(path, json) {
+            log::error!("cannot write {}: {}", path.display(), err);
+            return ExitCode::FAILURE;
         }
     }
 
-    let image_out = Image::from(&image_sp);
-    save_image(image_out, "./test/out.png");
+    if summary.failures == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn run_verify(args: &VerifyArgs) -> ExitCode {
+    let ruleset = match try_load_image(&args.sample) {
+        Ok(sample) => Ruleset::<8, Pattern8>::extract(&args.quantize.apply(sample)),
+        Err(_) => match Ruleset::load(&args.sample) {
+            Ok(ruleset) => ruleset,
+            Err(err) => {
+                log::error!("{}: {}", args.sample.display(), err);
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+    let output = match try_load_image(&args.output) {
+        Ok(output) => output,
+        Err(err) => {
+            log::error!("{}: {}", args.output.display(), err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let violations = verify(&ruleset, &output, args.open_border);
+    for pos in &violations {
+        println!("{} {}", pos.x, pos.y);
+    }
+
+    if violations.is_empty() {
+        log::info!("all {} pixels are valid", output.colors.len());
+        ExitCode::SUCCESS
+    } else {
+        log::error!(
+            "{} of {} pixels are invalid",
+            violations.len(),
+            output.colors.len()
+        );
+        ExitCode::FAILURE
+    }
+}
+
+fn run_text(args: &TextArgs) -> ExitCode {
+    let sample = match load_text(&args.sample) {
+        Ok(sample) => sample,
+        Err(err) => {
+            log::error!("{}: {}", args.sample.display(), err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let seed = args.seed.unwrap_or_else(time_seed);
+    log::info!("seed: {}", seed);
+    let mut image_sp =
+        ImageSuperposition::<8, Pattern8<char>>::with_seed(args.width, args.height, seed);
+    image_sp.open_border = args.open_border;
+    image_sp.extract(sample);
+    image_sp.propagate_all();
+    let mut solver = Solver::new(image_sp);
+    let status = solver.run_with(&mut LogObserver);
+
+    let map = render_text(&solver.image_sp);
+    match &args.output {
+        Some(path) => {
+            if let Err(err) = save_text(&map, path) {
+                log::error!("{}: {}", path.display(), err);
+                return ExitCode::FAILURE;
+            }
+        }
+        None => print!("{}", to_text(&map)),
+    }
+
+    if status == Status::Solved {
+        ExitCode::SUCCESS
+    } else {
+        log::error!("ran into a contradiction");
+        ExitCode::FAILURE
+    }
+}
+
+fn run_voxel(args: &VoxelArgs) -> ExitCode {
+    let sample = match args.samples.as_slice() {
+        [path] if is_vox(path) => load_vox(path).map_err(|err| format!("{}: {}", path, err)),
+        _ => find_samples(&args.samples)
+            .map_err(|err| err.to_string())
+            .and_then(|paths| load_slices(&paths).map_err(|err| err.to_string())),
+    };
+    let sample = match sample {
+        Ok(sample) => sample,
+        Err(err) => {
+            log::error!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    log::info!(
+        "sample of {}x{}x{} voxels",
+        sample.width,
+        sample.height,
+        sample.depth
+    );
+
+    if is_vox(&args.output)
+        && let Err(err) = check_size(args.width, args.height, args.depth.unwrap_or(sample.depth))
+    {
+        log::error!("{}: {}", args.output, err);
+        return ExitCode::FAILURE;
+    }
+
+    let seed = args.seed.unwrap_or_else(time_seed);
+    log::info!("seed: {}", seed);
+    let (grid, status) = match args.neighborhood {
+        Neighborhood::Faces => solve_voxels::<6, Pattern6>(args, sample, seed),
+        Neighborhood::All => solve_voxels::<26, Pattern26>(args, sample, seed),
+    };
+
+    if let Some(dir) = Path::new(&args.output).parent()
+        && let Err(err) = fs::create_dir_all(dir)
+    {
+        log::error!("{}: {}", dir.display(), err);
+        return ExitCode::FAILURE;
+    }
+    let saved = if is_vox(&args.output) {
+        save_vox(&grid, &args.output).map_err(|err| err.to_string())
+    } else {
+        save_slices(&grid, &args.output)
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    };
+    if let Err(err) = saved {
+        log::error!("{}", err);
+        return ExitCode::FAILURE;
+    }
+
+    if status == Status::Solved {
+        ExitCode::SUCCESS
+    } else {
+        log::error!("ran into a contradiction");
+        ExitCode::FAILURE
+    }
+}
+
+/// Undecided and contradicting voxels are left empty, so that no placeholder color
+/// ends up as geometry.
+fn solve_voxels<const N: usize, T: VoxelPattern<N, Value = Color>>(
+    args: &VoxelArgs,
+    sample: VoxelGrid,
+    seed: u64,
+) -> (VoxelGrid, Status) {
+    let depth = args.depth.unwrap_or(sample.depth);
+    let mut image_sp = ImageSuperposition::<N, T>::with_depth(args.width, args.height, depth, seed);
+    image_sp.open_border = args.open_border;
+    image_sp.extract_voxels(&sample);
+    image_sp.propagate_all();
+    let mut solver = Solver::new(image_sp);
+    let status = solver.run_with(&mut LogObserver);
+
+    (solver.image_sp.to_voxels(EMPTY, EMPTY), status)
+}
+
+fn is_vox(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("vox"))
 }
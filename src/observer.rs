@@ -0,0 +1,222 @@
+use crate::{pattern::Pattern, stats::RunStats, superposition::ImageSuperposition, vec2::Vec2};
+
+/// Gets notified about the progress of a solver. Every callback receives the
+/// superposition after the event took place and does nothing by default.
+pub trait Observer<const N: usize, T: Pattern<N>> {
+    /// A pixel was collapsed to a single color.
+    fn on_collapse(
+        &mut self,
+        _image_sp: &ImageSuperposition<N, T>,
+        _pixel_index: usize,
+        _color: T::Value,
+    ) {
+    }
+
+    /// The possible colors of a pixel were revised during propagation.
+    fn on_propagate(&mut self, _image_sp: &ImageSuperposition<N, T>, _pixel_index: usize) {}
+
+    /// A color was removed from a pixel during propagation.
+    fn on_ban(
+        &mut self,
+        _image_sp: &ImageSuperposition<N, T>,
+        _pixel_index: usize,
+        _color: T::Value,
+    ) {
+    }
+
+    /// Propagation removed the last color of a pixel.
+    fn on_contradiction(&mut self, _image_sp: &ImageSuperposition<N, T>, _pixel_index: usize) {}
+
+    /// The solver restored a snapshot, `depth` snapshots are left on the stack.
+    fn on_backtrack(&mut self, _image_sp: &ImageSuperposition<N, T>, _depth: usize) {}
+
+    /// A collapse was propagated, after backtracking from any contradictions.
+    fn on_step(&mut self, _image_sp: &ImageSuperposition<N, T>) {}
+
+    /// The run was solved or ended in an unresolvable contradiction.
+    fn on_finished(&mut self, _image_sp: &ImageSuperposition<N, T>, _stats: &RunStats) {}
+}
+
+/// Ignores all events.
+impl<const N: usize, T: Pattern<N>> Observer<N, T> for () {}
+
+impl<const N: usize, T: Pattern<N>, O: Observer<N, T> + ?Sized> Observer<N, T> for &mut O {
+    fn on_collapse(
+        &mut self,
+        image_sp: &ImageSuperposition<N, T>,
+        pixel_index: usize,
+        color: T::Value,
+    ) {
+        (**self).on_collapse(image_sp, pixel_index, color);
+    }
+
+    fn on_ban(&mut self, image_sp: &ImageSuperposition<N, T>, pixel_index: usize, color: T::Value) {
+        (**self).on_ban(image_sp, pixel_index, color);
+    }
+
+    fn on_propagate(&mut self, image_sp: &ImageSuperposition<N, T>, pixel_index: usize) {
+        (**self).on_propagate(image_sp, pixel_index);
+    }
+
+    fn on_contradiction(&mut self, image_sp: &ImageSuperposition<N, T>, pixel_index: usize) {
+        (**self).on_contradiction(image_sp, pixel_index);
+    }
+
+    fn on_backtrack(&mut self, image_sp: &ImageSuperposition<N, T>, depth: usize) {
+        (**self).on_backtrack(image_sp, depth);
+    }
+
+    fn on_step(&mut self, image_sp: &ImageSuperposition<N, T>) {
+        (**self).on_step(image_sp);
+    }
+
+    fn on_finished(&mut self, image_sp: &ImageSuperposition<N, T>, stats: &RunStats) {
+        (**self).on_finished(image_sp, stats);
+    }
+}
+
+/// Logs every event: collapses and bans at trace level, contradictions and backtracks
+/// at debug level and the end of the run at info level.
+pub struct LogObserver;
+
+impl<const N: usize, T: Pattern<N>> Observer<N, T> for LogObserver {
+    fn on_collapse(
+        &mut self,
+        image_sp: &ImageSuperposition<N, T>,
+        pixel_index: usize,
+        color: T::Value,
+    ) {
+        let pos = Vec2::from_index(pixel_index, image_sp.width);
+        log::trace!("collapse at: {:?} to {:?}", pos, color);
+    }
+
+    fn on_ban(&mut self, image_sp: &ImageSuperposition<N, T>, pixel_index: usize, color: T::Value) {
+        let pos = Vec2::from_index(pixel_index, image_sp.width);
+        log::trace!("ban at: {:?}: {:?}", pos, color);
+    }
+
+    fn on_contradiction(&mut self, image_sp: &ImageSuperposition<N, T>, pixel_index: usize) {
+        let pos = Vec2::from_index(pixel_index, image_sp.width);
+        log::debug!("contradiction at: {:?}", pos);
+    }
+
+    fn on_backtrack(&mut self, _: &ImageSuperposition<N, T>, depth: usize) {
+        log::debug!("restore, stack size: {}", depth);
+    }
+
+    fn on_finished(&mut self, _: &ImageSuperposition<N, T>, stats: &RunStats) {
+        log::info!(
+            "finished after {} collapses and {} backtracks, dead pixels: {}",
+            stats.collapses,
+            stats.backtracks,
+            stats.dead_pixels
+        );
+    }
+}
+
+/// Forwards every event to both observers, first to `A`, then to `B`.
+impl<const N: usize, T: Pattern<N>, A: Observer<N, T>, B: Observer<N, T>> Observer<N, T>
+    for (A, B)
+{
+    fn on_collapse(
+        &mut self,
+        image_sp: &ImageSuperposition<N, T>,
+        pixel_index: usize,
+        color: T::Value,
+    ) {
+        self.0.on_collapse(image_sp, pixel_index, color);
+        self.1.on_collapse(image_sp, pixel_index, color);
+    }
+
+    fn on_ban(&mut self, image_sp: &ImageSuperposition<N, T>, pixel_index: usize, color: T::Value) {
+        self.0.on_ban(image_sp, pixel_index, color);
+        self.1.on_ban(image_sp, pixel_index, color);
+    }
+
+    fn on_propagate(&mut self, image_sp: &ImageSuperposition<N, T>, pixel_index: usize) {
+        self.0.on_propagate(image_sp, pixel_index);
+        self.1.on_propagate(image_sp, pixel_index);
+    }
+
+    fn on_contradiction(&mut self, image_sp: &ImageSuperposition<N, T>, pixel_index: usize) {
+        self.0.on_contradiction(image_sp, pixel_index);
+        self.1.on_contradiction(image_sp, pixel_index);
+    }
+
+    fn on_backtrack(&mut self, image_sp: &ImageSuperposition<N, T>, depth: usize) {
+        self.0.on_backtrack(image_sp, depth);
+        self.1.on_backtrack(image_sp, depth);
+    }
+
+    fn on_step(&mut self, image_sp: &ImageSuperposition<N, T>) {
+        self.0.on_step(image_sp);
+        self.1.on_step(image_sp);
+    }
+
+    fn on_finished(&mut self, image_sp: &ImageSuperposition<N, T>, stats: &RunStats) {
+        self.0.on_finished(image_sp, stats);
+        self.1.on_finished(image_sp, stats);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        color::Color,
+        image::Image,
+        pattern8::Pattern8,
+        solver::{Solver, Status},
+        superposition::Wfc,
+    };
+
+    #[derive(Default)]
+    struct EventLog {
+        collapses: Vec<(usize, Color)>,
+        bans: usize,
+        finished: Vec<RunStats>,
+    }
+
+    impl Observer<8, Pattern8> for EventLog {
+        fn on_collapse(
+            &mut self,
+            image_sp: &ImageSuperposition<8, Pattern8>,
+            pixel_index: usize,
+            color: Color,
+        ) {
+            assert_eq!(image_sp.pixels[pixel_index].colors[0].color, color);
+            self.collapses.push((pixel_index, color));
+        }
+
+        fn on_ban(&mut self, _: &ImageSuperposition<8, Pattern8>, _: usize, _: Color) {
+            self.bans += 1;
+        }
+
+        fn on_finished(&mut self, _: &ImageSuperposition<8, Pattern8>, stats: &RunStats) {
+            self.finished.push(stats.clone());
+        }
+    }
+
+    #[test]
+    fn it_reports_collapses_bans_and_the_end_of_the_run() {
+        let (b, w) = (Color(0xff000000), Color(0xffffffff));
+        let sample = Image {
+            width: 5,
+            height: 1,
+            colors: vec![b, w, b, w, b],
+        };
+        let mut image_sp = ImageSuperposition::<8, Pattern8>::with_seed(9, 1, 3);
+        image_sp.extract(sample);
+        let mut solver = Solver::new(image_sp);
+        let mut log = EventLog::default();
+
+        assert_eq!(solver.run_with(&mut log), Status::Solved);
+
+        assert!(!log.collapses.is_empty());
+        assert!(log.bans > 0);
+        assert_eq!(log.finished, vec![solver.stats.clone()]);
+        assert_eq!(solver.stats.collapses, log.collapses.len());
+        assert_eq!(solver.stats.bans, log.bans);
+        assert_eq!(solver.stats.dead_pixels, 0);
+    }
+}
@@ -1,12 +1,47 @@
-use crate::{color::Color, image::Image, stack_set::StackSet, vec2::Vec2};
-
-pub trait Pattern<const N: usize>: Sized + Clone {
-    fn add_neighbors(indices: &mut StackSet, index: usize, width: u32, height: u32);
-    fn empty() -> Self;
-    fn extract_pattern_at(image: &Image, pos: Vec2) -> Self;
-    fn get_colors(&self) -> &[Option<Color>; N];
-    fn get_color_at(&self, index: usize) -> Option<Color>;
-    fn get_neighbors(index: usize, width: u32, height: u32) -> Vec<usize>;
-    fn get_neighbors_opt(index: usize, width: u32, height: u32) -> Vec<Option<usize>>;
-    fn get_neighbors_and_colors(&self, index: usize, width: u32, height: u32) -> Vec<(Option<usize>, Option<Color>)>;
+use crate::{
+    cell::Cell, image::Image, stack_set::StackSet, vec2::Vec2, vec3::Vec3, voxel::VoxelGrid,
+};
+
+pub trait Pattern<const N: usize>: Sized + Clone + Send + Sync {
+    /// The value of a cell, e.g. the color of a pixel.
+    type Value: Cell;
+
+    /// The grid is `depth` layers of `width` x `height` cells, 2D patterns only ever
+    /// see a single layer.
+    fn add_neighbors(indices: &mut StackSet, index: usize, width: u32, height: u32, depth: u32);
+    fn extract_pattern_at(image: &Image<Self::Value>, pos: Vec2) -> Self;
+    fn get_neighbors(index: usize, width: u32, height: u32, depth: u32) -> Vec<usize>;
+    fn get_neighbors_opt(index: usize, width: u32, height: u32, depth: u32) -> Vec<Option<usize>>;
+    /// The index of the direction pointing back from the neighbor at `index`.
+    fn get_reverse_index(index: usize) -> usize;
+    /// Whether the pattern allows `color` at the neighbor `index`, None being outside the image.
+    fn matches(&self, index: usize, color: Option<Self::Value>) -> bool;
+    /// Whether the pattern around `color` can be next to the `other` pattern around
+    /// `other_color` at the neighbor `index`, i.e. whether both agree where they overlap.
+    fn agrees(
+        &self,
+        color: Self::Value,
+        index: usize,
+        other: &Self,
+        other_color: Self::Value,
+    ) -> bool {
+        self.matches(index, Some(other_color))
+            && other.matches(Self::get_reverse_index(index), Some(color))
+    }
+
+    fn weight(&self) -> usize {
+        1
+    }
+
+    /// Adds the weight of `other` if both describe the same neighborhood and returns
+    /// whether they did. Patterns which are never equal do not need to merge.
+    fn merge(&mut self, _other: &Self) -> bool {
+        false
+    }
+}
+
+/// A pattern with neighbors in all three dimensions, which can be learned from a
+/// voxel sample. Learned from an image, the image is a grid with a single layer.
+pub trait VoxelPattern<const N: usize>: Pattern<N> {
+    fn extract_voxel_pattern_at(grid: &VoxelGrid<Self::Value>, pos: Vec3) -> Self;
 }
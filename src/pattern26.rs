@@ -0,0 +1,202 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cell::Cell,
+    color::Color,
+    image::Image,
+    pattern::{Pattern, VoxelPattern},
+    stack_set::StackSet,
+    vec2::Vec2,
+    vec3::Vec3,
+    voxel::VoxelGrid,
+};
+
+const PATTERN_SIZE: usize = 26;
+
+/// The 3x3x3 window layer by layer and row by row, without the center.
+const DIRS: [Vec3; PATTERN_SIZE] = directions();
+
+const fn directions() -> [Vec3; PATTERN_SIZE] {
+    let mut dirs = [Vec3 { x: 0, y: 0, z: 0 }; PATTERN_SIZE];
+    let mut i = 0;
+    while i < PATTERN_SIZE {
+        // skip the center of the window
+        let w = if i < PATTERN_SIZE / 2 { i } else { i + 1 } as i32;
+        dirs[i] = Vec3 {
+            x: w % 3 - 1,
+            y: w / 3 % 3 - 1,
+            z: w / 9 - 1,
+        };
+        i += 1;
+    }
+    dirs
+}
+
+/// The 3D counterpart of `Pattern8`: all voxels sharing a face, an edge or a corner
+/// with the center.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Pattern26<C = Color> {
+    colors: [Option<C>; PATTERN_SIZE],
+    /// How often the pattern occurs in the sample.
+    weight: usize,
+}
+
+impl<C: Cell> Pattern26<C> {
+    pub fn get_colors(&self) -> &[Option<C>; PATTERN_SIZE] {
+        &self.colors
+    }
+
+    /// The voxel at `pos` relative to the center, which has `color`.
+    fn get_at(&self, color: C, pos: Vec3) -> Option<C> {
+        let w = window_index(pos);
+        match w.cmp(&(PATTERN_SIZE / 2)) {
+            Ordering::Less => self.colors[w],
+            Ordering::Equal => Some(color),
+            Ordering::Greater => self.colors[w - 1],
+        }
+    }
+}
+
+fn window_index(pos: Vec3) -> usize {
+    ((pos.z + 1) * 9 + (pos.y + 1) * 3 + pos.x + 1) as usize
+}
+
+impl<C: Cell> Pattern<PATTERN_SIZE> for Pattern26<C> {
+    type Value = C;
+
+    fn extract_pattern_at(image: &Image<C>, pos: Vec2) -> Self {
+        Self {
+            colors: DIRS.map(|dir| match dir.z {
+                0 => image.get_color_at(pos + Vec2 { x: dir.x, y: dir.y }),
+                _ => None,
+            }),
+            weight: 1,
+        }
+    }
+
+    fn add_neighbors(indices: &mut StackSet, index: usize, width: u32, height: u32, depth: u32) {
+        for neighbor in Self::get_neighbors(index, width, height, depth) {
+            indices.push(neighbor);
+        }
+    }
+
+    fn get_neighbors_opt(index: usize, width: u32, height: u32, depth: u32) -> Vec<Option<usize>> {
+        let pos = Vec3::from_index(index, width, height);
+        DIRS.iter()
+            .map(|dir| pos + *dir)
+            .map(|p| {
+                p.is_inside(width, height, depth)
+                    .then(|| p.into_index(width, height))
+            })
+            .collect()
+    }
+
+    fn get_neighbors(index: usize, width: u32, height: u32, depth: u32) -> Vec<usize> {
+        Self::get_neighbors_opt(index, width, height, depth)
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    fn get_reverse_index(index: usize) -> usize {
+        PATTERN_SIZE - 1 - index
+    }
+
+    fn matches(&self, index: usize, color: Option<C>) -> bool {
+        self.colors[index] == color
+    }
+
+    fn agrees(&self, color: C, index: usize, other: &Self, other_color: C) -> bool {
+        // the windows overlap where both are within one voxel of their centers
+        let dir = DIRS[index];
+        for z in dir.z.max(0) - 1..=dir.z.min(0) + 1 {
+            for y in dir.y.max(0) - 1..=dir.y.min(0) + 1 {
+                for x in dir.x.max(0) - 1..=dir.x.min(0) + 1 {
+                    let pos = Vec3 { x, y, z };
+                    let other_pos = Vec3 {
+                        x: x - dir.x,
+                        y: y - dir.y,
+                        z: z - dir.z,
+                    };
+                    if self.get_at(color, pos) != other.get_at(other_color, other_pos) {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    fn weight(&self) -> usize {
+        self.weight
+    }
+
+    fn merge(&mut self, other: &Self) -> bool {
+        if self.colors != other.colors {
+            return false;
+        }
+
+        self.weight += other.weight;
+        true
+    }
+}
+
+impl<C: Cell> VoxelPattern<PATTERN_SIZE> for Pattern26<C> {
+    fn extract_voxel_pattern_at(grid: &VoxelGrid<C>, pos: Vec3) -> Self {
+        Self {
+            colors: DIRS.map(|dir| grid.get_voxel_at(pos + dir)),
+            weight: 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CENTER: Vec3 = Vec3 { x: 1, y: 1, z: 1 };
+
+    /// A 3x3x3 grid without any repeating voxel.
+    fn sample() -> VoxelGrid<u8> {
+        VoxelGrid {
+            width: 3,
+            height: 3,
+            depth: 3,
+            voxels: (0..27).collect(),
+        }
+    }
+
+    #[test]
+    fn it_agrees_with_the_neighbors_it_was_extracted_next_to() {
+        let grid = sample();
+        let center = Pattern26::extract_voxel_pattern_at(&grid, CENTER);
+
+        for (index, dir) in DIRS.iter().enumerate() {
+            let pos = CENTER + *dir;
+            let neighbor = Pattern26::extract_voxel_pattern_at(&grid, pos);
+            let neighbor_color = grid.get_voxel_at(pos).unwrap();
+
+            assert_eq!(center.get_colors()[index], Some(neighbor_color));
+            let reverse = DIRS[Pattern26::<u8>::get_reverse_index(index)];
+            assert_eq!(reverse + *dir, Vec3 { x: 0, y: 0, z: 0 });
+            assert!(center.agrees(13, index, &neighbor, neighbor_color));
+        }
+    }
+
+    #[test]
+    fn it_disagrees_if_the_overlap_differs() {
+        let grid = sample();
+        let center = Pattern26::extract_voxel_pattern_at(&grid, CENTER);
+        let above = Pattern26::extract_voxel_pattern_at(&grid, CENTER + DIRS[21]);
+        let mut changed_above = above.clone();
+        // the voxel north of the center, which both windows contain
+        changed_above.colors[1] = Some(0);
+
+        assert_eq!(DIRS[21], Vec3 { x: 0, y: 0, z: 1 });
+        assert!(center.matches(21, Some(22)) && changed_above.matches(4, Some(13)));
+        assert!(!center.agrees(13, 21, &changed_above, 22));
+    }
+}
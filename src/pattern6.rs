@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cell::Cell,
+    color::Color,
+    image::Image,
+    pattern::{Pattern, VoxelPattern},
+    stack_set::StackSet,
+    vec2::Vec2,
+    vec3::Vec3,
+    voxel::VoxelGrid,
+};
+
+pub const BELOW: usize = 0;
+pub const NORTH: usize = 1;
+pub const WEST: usize = 2;
+pub const EAST: usize = 3;
+pub const SOUTH: usize = 4;
+pub const ABOVE: usize = 5;
+
+const PATTERN_SIZE: usize = 6;
+
+const DIRS: [Vec3; PATTERN_SIZE] = [
+    Vec3 { x: 0, y: 0, z: -1 },
+    Vec3 { x: 0, y: -1, z: 0 },
+    Vec3 { x: -1, y: 0, z: 0 },
+    Vec3 { x: 1, y: 0, z: 0 },
+    Vec3 { x: 0, y: 1, z: 0 },
+    Vec3 { x: 0, y: 0, z: 1 },
+];
+
+/// The voxels sharing a face with the center. The neighborhoods of adjacent voxels
+/// only overlap in their centers, so they agree if both centers match.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Pattern6<C = Color> {
+    colors: [Option<C>; PATTERN_SIZE],
+    /// How often the pattern occurs in the sample.
+    weight: usize,
+}
+
+impl<C: Cell> Pattern6<C> {
+    pub fn get_colors(&self) -> &[Option<C>; PATTERN_SIZE] {
+        &self.colors
+    }
+}
+
+impl<C: Cell> Pattern<PATTERN_SIZE> for Pattern6<C> {
+    type Value = C;
+
+    fn extract_pattern_at(image: &Image<C>, pos: Vec2) -> Self {
+        Self {
+            colors: DIRS.map(|dir| match dir.z {
+                0 => image.get_color_at(pos + Vec2 { x: dir.x, y: dir.y }),
+                _ => None,
+            }),
+            weight: 1,
+        }
+    }
+
+    fn add_neighbors(indices: &mut StackSet, index: usize, width: u32, height: u32, depth: u32) {
+        for neighbor in Self::get_neighbors(index, width, height, depth) {
+            indices.push(neighbor);
+        }
+    }
+
+    fn get_neighbors_opt(index: usize, width: u32, height: u32, depth: u32) -> Vec<Option<usize>> {
+        let pos = Vec3::from_index(index, width, height);
+        DIRS.iter()
+            .map(|dir| pos + *dir)
+            .map(|p| {
+                p.is_inside(width, height, depth)
+                    .then(|| p.into_index(width, height))
+            })
+            .collect()
+    }
+
+    fn get_neighbors(index: usize, width: u32, height: u32, depth: u32) -> Vec<usize> {
+        Self::get_neighbors_opt(index, width, height, depth)
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    fn get_reverse_index(index: usize) -> usize {
+        PATTERN_SIZE - 1 - index
+    }
+
+    fn matches(&self, index: usize, color: Option<C>) -> bool {
+        self.colors[index] == color
+    }
+
+    fn weight(&self) -> usize {
+        self.weight
+    }
+
+    fn merge(&mut self, other: &Self) -> bool {
+        if self.colors != other.colors {
+            return false;
+        }
+
+        self.weight += other.weight;
+        true
+    }
+}
+
+impl<C: Cell> VoxelPattern<PATTERN_SIZE> for Pattern6<C> {
+    fn extract_voxel_pattern_at(grid: &VoxelGrid<C>, pos: Vec3) -> Self {
+        Self {
+            colors: DIRS.map(|dir| grid.get_voxel_at(pos + dir)),
+            weight: 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_extracts_the_faces_of_a_voxel() {
+        // two layers of 2x1 voxels
+        let grid = VoxelGrid {
+            width: 2,
+            height: 1,
+            depth: 2,
+            voxels: vec![1, 2, 3, 4],
+        };
+
+        let pattern = Pattern6::extract_voxel_pattern_at(&grid, Vec3 { x: 0, y: 0, z: 1 });
+
+        assert_eq!(
+            pattern.get_colors(),
+            &[Some(1), None, None, Some(4), None, None]
+        );
+        for index in 0..PATTERN_SIZE {
+            assert_eq!(
+                DIRS[Pattern6::<u8>::get_reverse_index(index)],
+                Vec3 {
+                    x: -DIRS[index].x,
+                    y: -DIRS[index].y,
+                    z: -DIRS[index].z,
+                }
+            );
+        }
+        assert_eq!(
+            Pattern6::<u8>::get_neighbors_opt(3, 2, 1, 2),
+            vec![Some(1), None, Some(2), None, None, None]
+        );
+    }
+}
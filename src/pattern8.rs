@@ -1,4 +1,8 @@
-use crate::{color::Color, image::Image, pattern::Pattern, stack_set::StackSet, vec2::Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cell::Cell, color::Color, image::Image, pattern::Pattern, stack_set::StackSet, vec2::Vec2,
+};
 
 pub const NW: usize = 0;
 pub const N: usize = 1;
@@ -24,56 +28,62 @@ const DIRS: [Vec2; PATTERN_SIZE] = [
 
 const REVERSE_INDICES: [usize; PATTERN_SIZE] = [7, 6, 5, 4, 3, 2, 1, 0];
 
-const REVERSE_DIRS: [Vec2; PATTERN_SIZE] = [
-    Vec2 { x: 1, y: 1 },
-    Vec2 { x: 0, y: 1 },
-    Vec2 { x: -1, y: 1 },
-    Vec2 { x: 1, y: 0 },
-    Vec2 { x: -1, y: 0 },
-    Vec2 { x: 1, y: -1 },
-    Vec2 { x: 0, y: -1 },
-    Vec2 { x: -1, y: -1 },
-];
-
-#[derive(Clone, Debug)]
-pub struct Pattern8 {
-    colors: [Option<Color>; PATTERN_SIZE],
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Pattern8<C = Color> {
+    colors: [Option<C>; PATTERN_SIZE],
+    /// How often the pattern occurs in the sample.
+    weight: usize,
 }
 
-impl Pattern<PATTERN_SIZE> for Pattern8 {
-    fn get_colors(&self) -> &[Option<Color>; PATTERN_SIZE] {
-        &self.colors
+impl<C: Cell> Pattern8<C> {
+    pub fn empty() -> Self {
+        Pattern8 {
+            colors: [None; PATTERN_SIZE],
+            weight: 1,
+        }
     }
 
+    pub fn get_colors(&self) -> &[Option<C>; PATTERN_SIZE] {
+        &self.colors
+    }
 
-    fn get_color_at(&self, index: usize) -> Option<Color> {
+    pub fn get_color_at(&self, index: usize) -> Option<C> {
         self.colors[index]
     }
 
-    fn extract_pattern_at(image: &Image, pos: Vec2) -> Self {
-        let mut pattern = Pattern8 {
-            colors: [None; PATTERN_SIZE],
-        };
+    /// The 3x3 window row by row, with `color` in the center.
+    fn get_window(&self, color: C) -> [Option<C>; 9] {
+        let c = &self.colors;
+        [
+            c[NW],
+            c[N],
+            c[NE],
+            c[W],
+            Some(color),
+            c[E],
+            c[SW],
+            c[S],
+            c[SE],
+        ]
+    }
+}
 
-        for i in 0..DIRS.len() {
-            let dir = DIRS[i];
-            let color = image.get_color_at(pos + dir);
-            pattern.colors[i] = color;
-        }
+impl<C: Cell> Pattern<PATTERN_SIZE> for Pattern8<C> {
+    type Value = C;
 
-        pattern
-    }
+    fn extract_pattern_at(image: &Image<C>, pos: Vec2) -> Self {
+        let mut pattern = Self::empty();
 
-    fn empty() -> Self {
-        Pattern8 {
-            colors: [None; PATTERN_SIZE],
+        for (i, dir) in DIRS.iter().enumerate() {
+            pattern.colors[i] = image.get_color_at(pos + *dir);
         }
+
+        pattern
     }
 
-    fn add_neighbors(indices: &mut StackSet, index: usize, width: u32, height: u32) {
+    fn add_neighbors(indices: &mut StackSet, index: usize, width: u32, height: u32, _depth: u32) {
         let pos = Vec2::from_index(index, width);
-        for i in 0..DIRS.len() {
-            let dir = DIRS[i];
+        for dir in DIRS {
             let p = pos + dir;
 
             if p.is_inside(width, height) {
@@ -82,14 +92,13 @@ impl Pattern<PATTERN_SIZE> for Pattern8 {
         }
     }
 
-    fn get_neighbors_opt(index: usize, width: u32, height: u32) -> Vec<Option<usize>> {
+    fn get_neighbors_opt(index: usize, width: u32, height: u32, _depth: u32) -> Vec<Option<usize>> {
         // TODO: merge with add_neighbors?
         // TODO: return [Option<usize>; 8] ?
         let mut neighbors = Vec::new();
 
         let pos = Vec2::from_index(index, width);
-        for i in 0..DIRS.len() {
-            let dir = DIRS[i];
+        for dir in DIRS {
             let p = pos + dir;
 
             if p.is_inside(width, height) {
@@ -102,13 +111,12 @@ impl Pattern<PATTERN_SIZE> for Pattern8 {
         neighbors
     }
 
-    fn get_neighbors(index: usize, width: u32, height: u32) -> Vec<usize> {
+    fn get_neighbors(index: usize, width: u32, height: u32, _depth: u32) -> Vec<usize> {
         // TODO: merge with add_neighbors?
         let mut neighbors = Vec::new();
 
         let pos = Vec2::from_index(index, width);
-        for i in 0..DIRS.len() {
-            let dir = DIRS[i];
+        for dir in DIRS {
             let p = pos + dir;
 
             if p.is_inside(width, height) {
@@ -119,28 +127,105 @@ impl Pattern<PATTERN_SIZE> for Pattern8 {
         neighbors
     }
 
-    fn get_neighbors_and_colors(
-        &self,
-        index: usize,
-        width: u32,
-        height: u32,
-    ) -> Vec<(Option<usize>, Option<Color>)> {
-        let mut neighbors_and_colors = Vec::new();
+    fn get_reverse_index(index: usize) -> usize {
+        REVERSE_INDICES[index]
+    }
 
-        let pos = Vec2::from_index(index, width);
-        for i in 0..DIRS.len() {
-            let dir = DIRS[i];
-            let p = pos + dir;
+    fn matches(&self, index: usize, color: Option<C>) -> bool {
+        self.colors[index] == color
+    }
 
-            let color = self.colors[i];
-            if p.is_inside(width, height) {
-                let neighbor = p.into_index(width);
-                neighbors_and_colors.push((Some(neighbor), color));
-            } else {
-                neighbors_and_colors.push((None, color));
+    fn agrees(&self, color: C, index: usize, other: &Self, other_color: C) -> bool {
+        let own = self.get_window(color);
+        let others = other.get_window(other_color);
+
+        // the windows overlap where both are within one pixel of their centers
+        let Vec2 { x: dx, y: dy } = DIRS[index];
+        for y in dy.max(0) - 1..=dy.min(0) + 1 {
+            for x in dx.max(0) - 1..=dx.min(0) + 1 {
+                let own_index = ((y + 1) * 3 + x + 1) as usize;
+                let others_index = ((y - dy + 1) * 3 + x - dx + 1) as usize;
+                if own[own_index] != others[others_index] {
+                    return false;
+                }
             }
         }
 
-        neighbors_and_colors
+        true
+    }
+
+    fn weight(&self) -> usize {
+        self.weight
+    }
+
+    fn merge(&mut self, other: &Self) -> bool {
+        if self.colors != other.colors {
+            return false;
+        }
+
+        self.weight += other.weight;
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BLACK: Color = Color(0xff000000);
+    const WHITE: Color = Color(0xffffffff);
+
+    /// B W B
+    /// W B W
+    /// B W W
+    ///
+    /// with B black and W white.
+    fn sample() -> Image {
+        Image {
+            width: 3,
+            height: 3,
+            colors: vec![
+                BLACK, WHITE, BLACK, WHITE, BLACK, WHITE, BLACK, WHITE, WHITE,
+            ],
+        }
+    }
+
+    #[test]
+    fn it_agrees_with_the_neighbors_it_was_extracted_next_to() {
+        let image = sample();
+        let center = Pattern8::extract_pattern_at(&image, Vec2 { x: 1, y: 1 });
+
+        for (index, dir) in DIRS.iter().enumerate() {
+            let pos = Vec2 { x: 1, y: 1 } + *dir;
+            let neighbor = Pattern8::extract_pattern_at(&image, pos);
+            let neighbor_color = image.get_color_at(pos).unwrap();
+
+            assert!(center.agrees(BLACK, index, &neighbor, neighbor_color));
+        }
+    }
+
+    #[test]
+    fn it_disagrees_if_the_overlap_differs() {
+        let image = sample();
+        let center = Pattern8::extract_pattern_at(&image, Vec2 { x: 1, y: 1 });
+        let east = Pattern8::extract_pattern_at(&image, Vec2 { x: 2, y: 1 });
+        let mut changed_east = east.clone();
+        changed_east.colors[S] = Some(BLACK);
+
+        // only the colors of both centers are compared without the overlap
+        assert!(center.matches(E, Some(WHITE)) && changed_east.matches(W, Some(BLACK)));
+        assert!(!center.agrees(BLACK, E, &changed_east, WHITE));
+    }
+
+    #[test]
+    fn it_merges_equal_patterns() {
+        let mut pattern = Pattern8::empty();
+
+        assert!(pattern.merge(&Pattern8::empty()));
+        assert!(!pattern.merge(&Pattern8::extract_pattern_at(
+            &sample(),
+            Vec2 { x: 1, y: 1 }
+        )));
+        assert_eq!(pattern.weight(), 2);
     }
 }
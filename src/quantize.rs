@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+
+use crate::{color::Color, image::Image};
+
+/// K-means stops after this many iterations even if the centers still move.
+const MAX_ITERATIONS: usize = 16;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Method {
+    /// Splits the color space at the median of the widest channel until there are
+    /// enough boxes and uses the average color of every box.
+    #[default]
+    MedianCut,
+    /// Refines the median cut palette by moving every color to the average of the
+    /// sample colors closest to it.
+    KMeans,
+}
+
+/// How to reduce the colors of a noisy sample before the ruleset is extracted, as
+/// every distinct color becomes a color of the superposition.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Quantize {
+    pub method: Method,
+    /// The maximum number of colors, none to keep all of them.
+    pub colors: Option<usize>,
+    /// Colors whose channels all differ by at most this much are snapped together
+    /// before the colors are reduced, zero to disable snapping.
+    pub tolerance: u8,
+}
+
+impl Quantize {
+    pub fn is_enabled(&self) -> bool {
+        self.colors.is_some() || self.tolerance > 0
+    }
+
+    /// Snaps near-identical colors and then reduces the image to at most `colors`
+    /// colors, returning the image and the palette it ends up with.
+    pub fn apply(&self, mut image: Image) -> (Image, Palette) {
+        if self.tolerance > 0 {
+            image = Palette::snap(&image, self.tolerance).apply(&image);
+        }
+
+        if let Some(count) = self.colors {
+            let palette = match self.method {
+                Method::MedianCut => Palette::median_cut(&image, count),
+                Method::KMeans => Palette::k_means(&image, count),
+            };
+            image = palette.apply(&image);
+        }
+
+        let palette = Palette::of(&image);
+        (image, palette)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub colors: Vec<Color>,
+}
+
+impl Palette {
+    /// The distinct colors of the image, the most frequent first.
+    pub fn of(image: &Image) -> Self {
+        Self {
+            colors: histogram(image)
+                .into_iter()
+                .map(|(color, _)| color)
+                .collect(),
+        }
+    }
+
+    /// Keeps the most frequent color of every group of colors within the tolerance.
+    pub fn snap(image: &Image, tolerance: u8) -> Self {
+        let mut colors: Vec<Color> = Vec::new();
+        for (color, _) in histogram(image) {
+            if !colors
+                .iter()
+                .any(|kept| is_within_tolerance(*kept, color, tolerance))
+            {
+                colors.push(color);
+            }
+        }
+        Self { colors }
+    }
+
+    pub fn median_cut(image: &Image, count: usize) -> Self {
+        Self {
+            colors: median_cut(histogram(image), count),
+        }
+    }
+
+    /// Starts from the median cut palette, so the result does not depend on a seed.
+    pub fn k_means(image: &Image, count: usize) -> Self {
+        let histogram = histogram(image);
+        let mut centers = median_cut(histogram.clone(), count);
+
+        for _ in 0..MAX_ITERATIONS {
+            let mut clusters = vec![Vec::new(); centers.len()];
+            for &(color, n) in &histogram {
+                clusters[nearest_index(&centers, color)].push((color, n));
+            }
+
+            let next: Vec<Color> = clusters
+                .iter()
+                .zip(&centers)
+                .map(|(cluster, center)| {
+                    if cluster.is_empty() {
+                        *center
+                    } else {
+                        average(cluster)
+                    }
+                })
+                .collect();
+            if next == centers {
+                break;
+            }
+            centers = next;
+        }
+
+        Self { colors: centers }
+    }
+
+    /// The palette color closest to the color.
+    pub fn nearest(&self, color: Color) -> Color {
+        self.colors[nearest_index(&self.colors, color)]
+    }
+
+    /// Replaces every color of the image by the nearest palette color.
+    pub fn apply(&self, image: &Image) -> Image {
+        let mut nearest: HashMap<Color, Color> = HashMap::new();
+        let colors = image
+            .colors
+            .iter()
+            .map(|&color| *nearest.entry(color).or_insert_with(|| self.nearest(color)))
+            .collect();
+
+        Image {
+            width: image.width,
+            height: image.height,
+            colors,
+        }
+    }
+}
+
+/// The distinct colors of the image with their number of pixels, the most frequent
+/// first and ties broken by the color to stay deterministic.
+fn histogram(image: &Image) -> Vec<(Color, usize)> {
+    let mut counts: HashMap<Color, usize> = HashMap::new();
+    for &color in &image.colors {
+        *counts.entry(color).or_default() += 1;
+    }
+
+    let mut histogram: Vec<(Color, usize)> = counts.into_iter().collect();
+    histogram.sort_by_key(|&(color, n)| (usize::MAX - n, color.0));
+    histogram
+}
+
+fn median_cut(histogram: Vec<(Color, usize)>, count: usize) -> Vec<Color> {
+    assert!(count > 0, "palette needs at least one color");
+    if histogram.is_empty() {
+        return Vec::new();
+    }
+
+    let mut boxes = vec![histogram];
+    while boxes.len() < count {
+        // split the box with the widest range in any channel
+        let Some((index, channel, _)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, entries)| entries.len() > 1)
+            .map(|(i, entries)| {
+                let (channel, range) = widest_channel(entries);
+                (i, channel, range)
+            })
+            .max_by_key(|&(_, _, range)| range)
+        else {
+            break;
+        };
+
+        let mut lower = boxes.swap_remove(index);
+        lower.sort_by_key(|&(color, _)| (channels(color)[channel], color.0));
+
+        let total: usize = lower.iter().map(|&(_, n)| n).sum();
+        let mut seen = 0;
+        let median = lower
+            .iter()
+            .position(|&(_, n)| {
+                seen += n;
+                seen * 2 >= total
+            })
+            .unwrap_or(0);
+        // both halves keep at least one color
+        let upper = lower.split_off((median + 1).min(lower.len() - 1));
+
+        boxes.push(lower);
+        boxes.push(upper);
+    }
+
+    boxes.iter().map(|entries| average(entries)).collect()
+}
+
+/// The channel with the largest difference between its lowest and highest value.
+fn widest_channel(entries: &[(Color, usize)]) -> (usize, u8) {
+    (0..4)
+        .map(|channel| {
+            let values = entries.iter().map(|&(color, _)| channels(color)[channel]);
+            let range = values.clone().max().unwrap() - values.min().unwrap();
+            (channel, range)
+        })
+        .max_by_key(|&(channel, range)| (range, usize::MAX - channel))
+        .unwrap()
+}
+
+/// The average of the colors weighted by their number of pixels.
+fn average(entries: &[(Color, usize)]) -> Color {
+    let mut sums = [0usize; 4];
+    let mut total = 0;
+    for &(color, n) in entries {
+        for (sum, value) in sums.iter_mut().zip(channels(color)) {
+            *sum += value as usize * n;
+        }
+        total += n;
+    }
+
+    let [r, g, b, a] = sums.map(|sum| ((sum + total / 2) / total) as u8);
+    Color::from_rgba(r, g, b, a)
+}
+
+fn nearest_index(colors: &[Color], color: Color) -> usize {
+    (0..colors.len())
+        .min_by_key(|&i| distance(colors[i], color))
+        .expect("palette is not empty")
+}
+
+fn distance(a: Color, b: Color) -> u32 {
+    channels(a)
+        .into_iter()
+        .zip(channels(b))
+        .map(|(a, b)| (a.abs_diff(b) as u32).pow(2))
+        .sum()
+}
+
+fn is_within_tolerance(a: Color, b: Color, tolerance: u8) -> bool {
+    channels(a)
+        .into_iter()
+        .zip(channels(b))
+        .all(|(a, b)| a.abs_diff(b) <= tolerance)
+}
+
+fn channels(color: Color) -> [u8; 4] {
+    [color.r(), color.g(), color.b(), color.a()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn gray(value: u8) -> Color {
+        Color::from_rgba(value, value, value, 255)
+    }
+
+    fn noisy_sample() -> Image {
+        // two dark and two light shades, the first of each pair the most frequent
+        let colors = [
+            gray(10),
+            gray(10),
+            gray(12),
+            gray(240),
+            gray(240),
+            gray(243),
+        ];
+        Image {
+            width: 6,
+            height: 1,
+            colors: colors.to_vec(),
+        }
+    }
+
+    #[test]
+    fn it_snaps_colors_within_the_tolerance() {
+        let (image, palette) = Quantize {
+            tolerance: 3,
+            ..Default::default()
+        }
+        .apply(noisy_sample());
+
+        assert_eq!(palette.colors, vec![gray(10), gray(240)]);
+        assert_eq!(image.colors[2], gray(10));
+        assert_eq!(image.colors[5], gray(240));
+    }
+
+    #[test]
+    fn it_reduces_the_colors_with_median_cut() {
+        let palette = Palette::median_cut(&noisy_sample(), 2);
+
+        assert_eq!(palette.colors.len(), 2);
+        assert_eq!(palette.nearest(gray(0)), gray(11));
+        assert_eq!(palette.nearest(gray(255)), gray(241));
+    }
+
+    #[test]
+    fn it_reduces_the_colors_with_k_means() {
+        for count in 1..=4 {
+            let (image, palette) = Quantize {
+                method: Method::KMeans,
+                colors: Some(count),
+                tolerance: 0,
+            }
+            .apply(noisy_sample());
+
+            assert_eq!(palette.colors.len(), count);
+            assert!(
+                image
+                    .colors
+                    .iter()
+                    .all(|color| palette.colors.contains(color))
+            );
+        }
+    }
+
+    #[test]
+    fn it_keeps_images_with_few_colors() {
+        let sample = noisy_sample();
+
+        let (image, palette) = Quantize {
+            colors: Some(8),
+            ..Default::default()
+        }
+        .apply(sample.clone());
+
+        assert_eq!(image, sample);
+        assert_eq!(palette.colors.len(), 4);
+    }
+
+    #[test]
+    fn it_reduces_empty_images_to_an_empty_palette() {
+        let empty = Image {
+            width: 0,
+            height: 0,
+            colors: Vec::new(),
+        };
+
+        for method in [Method::MedianCut, Method::KMeans] {
+            let (image, palette) = Quantize {
+                colors: Some(2),
+                tolerance: 8,
+                method,
+            }
+            .apply(empty.clone());
+
+            assert_eq!(image, empty);
+            assert!(palette.colors.is_empty());
+        }
+    }
+}
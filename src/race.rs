@@ -0,0 +1,98 @@
+use std::{
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+};
+
+use crate::{
+    pattern::Pattern,
+    solver::{Solver, Status},
+    superposition::ImageSuperposition,
+};
+
+/// Solves a copy of the superposition for every seed, each on its own thread, and
+/// returns the first solver which succeeds. The others are cancelled at their next
+/// step. The winning seed is `solver.stats.seed`, solving the superposition with
+/// only that seed gives the same result.
+pub fn race<const N: usize, T: Pattern<N>>(
+    image_sp: &ImageSuperposition<N, T>,
+    seeds: &[u64],
+) -> Option<Solver<N, T>> {
+    let is_solved = AtomicBool::new(false);
+    let winner = Mutex::new(None);
+
+    thread::scope(|scope| {
+        for &seed in seeds {
+            let (is_solved, winner) = (&is_solved, &winner);
+            scope.spawn(move || {
+                let mut image_sp = image_sp.clone();
+                image_sp.reseed(seed);
+                let mut solver = Solver::new(image_sp);
+
+                loop {
+                    if is_solved.load(Ordering::Relaxed) {
+                        return;
+                    }
+
+                    match solver.step() {
+                        Status::Running => {}
+                        Status::Solved => break,
+                        Status::Contradiction => {
+                            log::debug!("seed {} ran into a contradiction", seed);
+                            return;
+                        }
+                    }
+                }
+
+                if !is_solved.swap(true, Ordering::Relaxed) {
+                    *winner.lock().unwrap() = Some(solver);
+                }
+            });
+        }
+    });
+
+    let winner = winner.into_inner().unwrap();
+    if let Some(solver) = &winner {
+        log::info!("seed {} won", solver.stats.seed);
+    }
+    winner
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{image::load_image, pattern8::Pattern8};
+
+    fn is_send<T: Send>() {}
+
+    #[test]
+    fn it_is_sendable_across_threads() {
+        is_send::<ImageSuperposition<8, Pattern8>>();
+        is_send::<Solver<8, Pattern8>>();
+    }
+
+    #[test]
+    fn it_reproduces_the_winner_with_its_seed() {
+        let sample = load_image("./test/Water.png");
+        let prepared = Solver::<8, Pattern8>::from_sample(sample.clone(), 12, 12, 0);
+
+        let winner = race(&prepared.image_sp, &[11, 12, 13, 14]).unwrap();
+
+        let seed = winner.stats.seed;
+        assert!([11, 12, 13, 14].contains(&seed));
+
+        let mut replay = Solver::<8, Pattern8>::from_sample(sample, 12, 12, seed);
+        assert_eq!(replay.run(), Status::Solved);
+        let colors = |solver: &Solver<8, Pattern8>| -> Vec<_> {
+            solver
+                .image_sp
+                .pixels
+                .iter()
+                .map(|p| p.colors[0].color)
+                .collect()
+        };
+        assert_eq!(colors(&winner), colors(&replay));
+    }
+}
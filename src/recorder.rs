@@ -0,0 +1,158 @@
+use std::{fs, fs::File, io, io::BufWriter, path::Path};
+
+use ::image::{
+    Delay, Frame, ImageError, ImageResult,
+    codecs::gif::{GifEncoder, Repeat},
+};
+
+use crate::{
+    color::Color,
+    image::{Image, PixelFormat, to_rgba_image, try_save_image},
+    observer::Observer,
+    pattern::Pattern,
+    render::{RenderMode, render},
+    superposition::ImageSuperposition,
+};
+
+/// Captures the state of the superposition every `interval` steps, once the collapse
+/// of the step has been propagated.
+pub struct Recorder {
+    interval: usize,
+    render_mode: RenderMode,
+    steps: usize,
+    frames: Vec<Image>,
+}
+
+impl Recorder {
+    pub fn new(interval: usize, render_mode: RenderMode) -> Self {
+        assert!(interval > 0, "interval must be positive");
+
+        Self {
+            interval,
+            render_mode,
+            steps: 0,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Captures a frame regardless of the interval, e.g. the final result.
+    pub fn capture<const N: usize, T: Pattern<N, Value = Color>>(
+        &mut self,
+        image_sp: &ImageSuperposition<N, T>,
+    ) {
+        self.frames.push(render(image_sp, self.render_mode));
+    }
+
+    pub fn frames(&self) -> &[Image] {
+        &self.frames
+    }
+
+    pub fn save_gif<P: AsRef<Path>>(&self, path: P, frame_delay_ms: u32) -> ImageResult<()> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = GifEncoder::new(file);
+        encoder.set_repeat(Repeat::Infinite)?;
+
+        let delay = Delay::from_numer_denom_ms(frame_delay_ms, 1);
+        let frames = self
+            .frames
+            .iter()
+            .map(|image| Frame::from_parts(to_rgba_image(image), 0, 0, delay));
+        encoder.encode_frames(frames)
+    }
+
+    /// Saves the frames as `frame00000.png`, `frame00001.png`, ... into `dir`.
+    pub fn save_png_sequence<P: AsRef<Path>>(&self, dir: P) -> io::Result<()> {
+        fs::create_dir_all(&dir)?;
+        for (i, image) in self.frames.iter().enumerate() {
+            let path = dir.as_ref().join(format!("frame{:05}.png", i));
+            try_save_image(image, path, PixelFormat::Rgba8).map_err(|err| match err {
+                ImageError::IoError(err) => err,
+                err => io::Error::other(err),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl<const N: usize, T: Pattern<N, Value = Color>> Observer<N, T> for Recorder {
+    fn on_step(&mut self, image_sp: &ImageSuperposition<N, T>) {
+        if self.steps.is_multiple_of(self.interval) {
+            self.capture(image_sp);
+        }
+        self.steps += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        pattern8::Pattern8,
+        solver::{Solver, Status},
+        superposition::Wfc,
+    };
+
+    fn create_solver() -> Solver<8, Pattern8> {
+        let (b, w) = (Color(0xff000000), Color(0xffffffff));
+        let sample = Image {
+            width: 5,
+            height: 1,
+            colors: vec![b, w, b, w, b],
+        };
+        let mut image_sp = ImageSuperposition::<8, Pattern8>::with_seed(9, 1, 3);
+        image_sp.extract(sample);
+        Solver::new(image_sp)
+    }
+
+    #[test]
+    fn it_captures_every_nth_step() {
+        let mut solver = create_solver();
+        let mut every_step = Recorder::new(1, RenderMode::Average);
+        let mut every_other_step = Recorder::new(2, RenderMode::Average);
+
+        let status = solver.run_with(&mut (&mut every_step, &mut every_other_step));
+
+        assert_eq!(status, Status::Solved);
+        assert_eq!(every_step.frames().len(), solver.stats.collapses);
+        assert_eq!(
+            every_other_step.frames().len(),
+            every_step.frames().len().div_ceil(2)
+        );
+    }
+
+    #[test]
+    fn it_captures_after_propagation() {
+        let mut solver = create_solver();
+        let mut recorder = Recorder::new(1, RenderMode::Average);
+
+        solver.step_with(&mut recorder);
+
+        // a single collapse decides the whole alternating line
+        assert_eq!(
+            recorder.frames(),
+            &[render(&solver.image_sp, RenderMode::Average)]
+        );
+        assert!(
+            solver
+                .image_sp
+                .pixels
+                .iter()
+                .all(|pixel_sp| pixel_sp.colors.len() == 1)
+        );
+    }
+
+    #[test]
+    fn it_encodes_an_animated_gif() {
+        let mut solver = create_solver();
+        let mut recorder = Recorder::new(1, RenderMode::Average);
+        solver.run_with(&mut recorder);
+        recorder.capture(&solver.image_sp);
+
+        let path = std::env::temp_dir().join(format!("wfc-recorder-{}.gif", std::process::id()));
+        recorder.save_gif(&path, 100).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        assert_eq!(&bytes[0..6], b"GIF89a");
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{
+    color::Color,
+    image::Image,
+    pattern::Pattern,
+    superposition::{
+        CONTRADICTION, ImageSuperposition, PixelSuperposition, calc_color_weight, calc_entropy,
+    },
+};
+
+/// Magenta, which is not on the ramp of the heatmaps.
+const HEATMAP_CONTRADICTION: Color = Color::from_rgba(255, 0, 255, 255);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderMode {
+    /// Undecided pixels are blue and contradictions black, like `Image::from`.
+    Collapsed,
+    /// Undecided pixels show the weight-averaged color of their remaining colors.
+    Average,
+    /// The entropy of every pixel from black (collapsed) to white (highest entropy).
+    Entropy,
+    /// The number of remaining colors of every pixel from black (one) to white (most).
+    DomainSize,
+    /// The averaged image in grayscale with the contradictions in magenta.
+    Contradictions,
+}
+
+pub fn render<const N: usize, T: Pattern<N, Value = Color>>(
+    image_sp: &ImageSuperposition<N, T>,
+    mode: RenderMode,
+) -> Image {
+    let colors = match mode {
+        RenderMode::Collapsed => return Image::from(image_sp),
+        RenderMode::Average => image_sp.pixels.iter().map(average_color).collect(),
+        RenderMode::Entropy => {
+            let entropies: Vec<_> = image_sp
+                .pixels
+                .iter()
+                .map(|pixel_sp| (!pixel_sp.colors.is_empty()).then(|| calc_entropy(pixel_sp)))
+                .collect();
+            heatmap(&entropies)
+        }
+        RenderMode::DomainSize => {
+            let domain_sizes: Vec<_> = image_sp
+                .pixels
+                .iter()
+                .map(|pixel_sp| {
+                    (!pixel_sp.colors.is_empty()).then(|| (pixel_sp.colors.len() - 1) as f32)
+                })
+                .collect();
+            heatmap(&domain_sizes)
+        }
+        RenderMode::Contradictions => image_sp
+            .pixels
+            .iter()
+            .map(|pixel_sp| {
+                if pixel_sp.colors.is_empty() {
+                    return HEATMAP_CONTRADICTION;
+                }
+                let color = average_color(pixel_sp);
+                let luma = (0.299 * color.r() as f32
+                    + 0.587 * color.g() as f32
+                    + 0.114 * color.b() as f32) as u8;
+                Color::from_rgba(luma, luma, luma, 0xff)
+            })
+            .collect(),
+    };
+
+    Image {
+        width: image_sp.width,
+        height: image_sp.height,
+        colors,
+    }
+}
+
+fn average_color<const N: usize, T: Pattern<N, Value = Color>>(
+    pixel_sp: &PixelSuperposition<N, T>,
+) -> Color {
+    let mut sums = [0.0; 4];
+    let mut total_weight = 0.0;
+    for color_sp in &pixel_sp.colors {
+        let weight = calc_color_weight(color_sp) as f32;
+        let color = color_sp.color;
+        for (sum, channel) in sums
+            .iter_mut()
+            .zip([color.r(), color.g(), color.b(), color.a()])
+        {
+            *sum += weight * channel as f32;
+        }
+        total_weight += weight;
+    }
+
+    if total_weight == 0.0 {
+        return CONTRADICTION;
+    }
+
+    let [r, g, b, a] = sums.map(|sum| (sum / total_weight).round() as u8);
+    Color::from_rgba(r, g, b, a)
+}
+
+/// Maps the values relative to the highest value from black over red and yellow to white,
+/// None marks a contradiction in magenta.
+fn heatmap(values: &[Option<f32>]) -> Vec<Color> {
+    let max = values
+        .iter()
+        .flatten()
+        .fold(0.0f32, |max, &value| max.max(value));
+
+    values
+        .iter()
+        .map(|value| match value {
+            None => HEATMAP_CONTRADICTION,
+            Some(value) => {
+                let t = if max > 0.0 { value / max } else { 0.0 };
+                let channel = |offset: f32| ((3.0 * t - offset).clamp(0.0, 1.0) * 255.0) as u8;
+                Color::from_rgba(channel(0.0), channel(1.0), channel(2.0), 0xff)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        pattern8::Pattern8,
+        superposition::{ColorSuperposition, PixelSuperposition},
+    };
+
+    fn color_sp(color: Color, weight: usize) -> ColorSuperposition<8, Pattern8> {
+        ColorSuperposition {
+            color,
+            patterns: vec![Pattern8::empty(); weight],
+            weight,
+        }
+    }
+
+    /// An undecided pixel, a collapsed pixel and a contradiction.
+    fn create_image_sp() -> ImageSuperposition<8, Pattern8> {
+        let black = Color::from_rgba(0, 0, 0, 255);
+        let white = Color::from_rgba(255, 255, 255, 255);
+
+        let mut image_sp = ImageSuperposition::with_seed(3, 1, 0);
+        image_sp.pixels = vec![
+            PixelSuperposition {
+                colors: vec![color_sp(black, 1), color_sp(white, 3)],
+            },
+            PixelSuperposition {
+                colors: vec![color_sp(white, 1)],
+            },
+            PixelSuperposition { colors: vec![] },
+        ];
+        image_sp
+    }
+
+    #[test]
+    fn it_averages_the_remaining_colors_by_weight() {
+        let image = render(&create_image_sp(), RenderMode::Average);
+
+        assert_eq!(image.colors[0], Color::from_rgba(191, 191, 191, 255));
+        assert_eq!(image.colors[1], Color::from_rgba(255, 255, 255, 255));
+        assert_eq!(image.colors[2], CONTRADICTION);
+    }
+
+    #[test]
+    fn it_renders_heatmaps() {
+        let entropy = render(&create_image_sp(), RenderMode::Entropy);
+        let domain_size = render(&create_image_sp(), RenderMode::DomainSize);
+
+        for image in [entropy, domain_size] {
+            assert_eq!(image.colors[0], Color::from_rgba(255, 255, 255, 255));
+            assert_eq!(image.colors[1], Color::from_rgba(0, 0, 0, 255));
+            assert_eq!(image.colors[2], Color::from_rgba(255, 0, 255, 255));
+        }
+    }
+
+    #[test]
+    fn it_highlights_contradictions() {
+        let image = render(&create_image_sp(), RenderMode::Contradictions);
+
+        assert_eq!(image.colors[0], Color::from_rgba(191, 191, 191, 255));
+        let contradiction = image.colors[2];
+        assert_eq!(
+            (contradiction.r(), contradiction.g(), contradiction.b()),
+            (255, 0, 255)
+        );
+    }
+}
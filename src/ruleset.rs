@@ -0,0 +1,225 @@
+use std::{collections::HashMap, fmt, fs, path::Path};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    image::Image,
+    pattern::{Pattern, VoxelPattern},
+    superposition::{ColorSuperposition, calc_color_weight},
+    vec2::Vec2,
+    vec3::Vec3,
+    voxel::VoxelGrid,
+};
+
+const MAGIC: [u8; 4] = *b"WFCR";
+const VERSION: u32 = 2;
+
+#[derive(Debug)]
+pub enum RulesetError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Binary(bincode::Error),
+    InvalidHeader,
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for RulesetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RulesetError::Io(err) => write!(f, "cannot access ruleset: {}", err),
+            RulesetError::Json(err) => write!(f, "invalid json ruleset: {}", err),
+            RulesetError::Binary(err) => write!(f, "invalid binary ruleset: {}", err),
+            RulesetError::InvalidHeader => write!(f, "not a binary ruleset"),
+            RulesetError::UnsupportedVersion(version) => {
+                write!(f, "unsupported ruleset version {}", version)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RulesetError {}
+
+/// Everything learned from a sample: every color with its weight and the patterns
+/// which may surround it. The patterns double as the adjacency table of the color.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Ruleset<const N: usize, T: Pattern<N>> {
+    pub colors: Vec<ColorSuperposition<N, T>>,
+}
+
+impl<const N: usize, T: Pattern<N>> Ruleset<N, T> {
+    pub fn extract(image: &Image<T::Value>) -> Self {
+        // TODO: pixels at the borders have lower entropy: reduce possibilities
+        let mut ruleset = Self { colors: Vec::new() };
+        let mut color_indices: HashMap<T::Value, usize> = HashMap::new();
+
+        for y in 0..image.height as i32 {
+            for x in 0..image.width as i32 {
+                let color = image
+                    .get_color_at(Vec2 { x, y })
+                    .expect("image index not allowed");
+
+                let pattern = T::extract_pattern_at(image, Vec2 { x, y });
+                ruleset.add(&mut color_indices, color, pattern);
+            }
+        }
+
+        ruleset
+    }
+
+    /// Like `extract`, but learns the 3D neighborhood of every voxel.
+    pub fn extract_voxels(grid: &VoxelGrid<T::Value>) -> Self
+    where
+        T: VoxelPattern<N>,
+    {
+        let mut ruleset = Self { colors: Vec::new() };
+        let mut color_indices: HashMap<T::Value, usize> = HashMap::new();
+
+        for (i, &color) in grid.voxels.iter().enumerate() {
+            let pos = Vec3::from_index(i, grid.width, grid.height);
+            let pattern = T::extract_voxel_pattern_at(grid, pos);
+            ruleset.add(&mut color_indices, color, pattern);
+        }
+
+        ruleset
+    }
+
+    /// Adds the pattern to the color, merging it with an equal one.
+    fn add(&mut self, color_indices: &mut HashMap<T::Value, usize>, color: T::Value, pattern: T) {
+        let colors = &mut self.colors;
+        match color_indices.get(&color) {
+            Some(&color_index) => {
+                let patterns = &mut colors[color_index].patterns;
+                if !patterns.iter_mut().any(|existing| existing.merge(&pattern)) {
+                    patterns.push(pattern);
+                }
+                colors[color_index].weight = calc_color_weight(&colors[color_index]);
+            }
+            None => {
+                color_indices.insert(color, colors.len());
+                colors.push(ColorSuperposition {
+                    color,
+                    weight: pattern.weight(),
+                    patterns: vec![pattern],
+                });
+            }
+        }
+    }
+}
+
+impl<const N: usize, T: Pattern<N> + Serialize + DeserializeOwned> Ruleset<N, T> {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("ruleset is serializable")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, RulesetError> {
+        serde_json::from_str(json).map_err(RulesetError::Json)
+    }
+
+    /// Encodes the ruleset as magic bytes, format version and the bincode encoded ruleset.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(VERSION.to_le_bytes());
+        bytes.extend(bincode::serialize(self).expect("ruleset is serializable"));
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RulesetError> {
+        if bytes.len() < 8 || bytes[0..4] != MAGIC {
+            return Err(RulesetError::InvalidHeader);
+        }
+
+        let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        if version != VERSION {
+            return Err(RulesetError::UnsupportedVersion(version));
+        }
+
+        bincode::deserialize(&bytes[8..]).map_err(RulesetError::Binary)
+    }
+
+    /// Saves as json if the file extension is `.json`, in the binary format otherwise.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), RulesetError> {
+        let bytes = if is_json(path.as_ref()) {
+            self.to_json().into_bytes()
+        } else {
+            self.to_bytes()
+        };
+
+        fs::write(path, bytes).map_err(RulesetError::Io)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, RulesetError> {
+        let bytes = fs::read(path.as_ref()).map_err(RulesetError::Io)?;
+
+        if is_json(path.as_ref()) {
+            let json = String::from_utf8_lossy(&bytes);
+            Self::from_json(&json)
+        } else {
+            Self::from_bytes(&bytes)
+        }
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "json")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        color::Color,
+        pattern8::Pattern8,
+        superposition::{ImageSuperposition, Wfc},
+    };
+
+    fn sample() -> Image {
+        Image {
+            width: 3,
+            height: 2,
+            colors: vec![
+                Color(0xff0000ff),
+                Color(0xff00ff00),
+                Color(0xff0000ff),
+                Color(0xffff0000),
+                Color(0xff0000ff),
+                Color(0xff0000ff),
+            ],
+        }
+    }
+
+    #[test]
+    fn it_extracts_the_same_as_the_superposition() {
+        let ruleset = Ruleset::<8, Pattern8>::extract(&sample());
+        let mut image_sp = ImageSuperposition::<8, Pattern8>::with_seed(4, 4, 0);
+        image_sp.extract(sample());
+
+        assert_eq!(ruleset.colors.len(), 3);
+        assert_eq!(ruleset.colors[0].weight, 4);
+        assert_eq!(image_sp.pixels[5].colors, ruleset.colors);
+    }
+
+    #[test]
+    fn it_writes_readable_json() {
+        let ruleset = Ruleset::<8, Pattern8>::extract(&sample());
+
+        let json = ruleset.to_json();
+
+        assert!(json.contains("\"color\": \"#ff0000ff\""));
+        assert_eq!(Ruleset::from_json(&json).unwrap(), ruleset);
+    }
+
+    #[test]
+    fn it_round_trips_the_binary_format() {
+        let ruleset = Ruleset::<8, Pattern8>::extract(&sample());
+
+        let bytes = ruleset.to_bytes();
+
+        assert!(bytes.len() < ruleset.to_json().len());
+        assert_eq!(Ruleset::from_bytes(&bytes).unwrap(), ruleset);
+        assert!(matches!(
+            Ruleset::<8, Pattern8>::from_bytes(b"nope"),
+            Err(RulesetError::InvalidHeader)
+        ));
+    }
+}
@@ -1,11 +1,15 @@
+use serde::{Deserialize, Serialize};
+
 use crate::{pattern::Pattern, superposition::ImageSuperposition};
 
+#[derive(Serialize, Deserialize)]
 pub struct Snapshot<const N: usize, T: Pattern<N>> {
     pub image_sp: ImageSuperposition<N, T>,
     pub collapse_pixel_index: usize,
     pub collapse_color_index: usize,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct SnapshotStack<const N: usize, T: Pattern<N>> {
     stack: Vec<Snapshot<N, T>>,
 }
@@ -37,6 +41,16 @@ impl<const N: usize, T: Pattern<N>> SnapshotStack<N, T> {
     pub fn len(&self) -> usize {
         self.stack.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+}
+
+impl<const N: usize, T: Pattern<N>> Default for SnapshotStack<N, T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 //fn get_collapse_color_index<const N: usize, T: Pattern<N>>(
@@ -0,0 +1,294 @@
+use std::{fmt, fs, path::Path, time::Instant};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    image::Image,
+    observer::Observer,
+    pattern::{Pattern, VoxelPattern},
+    snapshot::{Snapshot, SnapshotStack},
+    stats::RunStats,
+    superposition::{ImageSuperposition, Wfc},
+    voxel::VoxelGrid,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    Running,
+    Solved,
+    Contradiction,
+}
+
+const CHECKPOINT_MAGIC: [u8; 4] = *b"WFCS";
+const CHECKPOINT_VERSION: u32 = 6;
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(std::io::Error),
+    Binary(bincode::Error),
+    InvalidHeader,
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckpointError::Io(err) => write!(f, "cannot access checkpoint: {}", err),
+            CheckpointError::Binary(err) => write!(f, "invalid checkpoint: {}", err),
+            CheckpointError::InvalidHeader => write!(f, "not a checkpoint"),
+            CheckpointError::UnsupportedVersion(version) => {
+                write!(f, "unsupported checkpoint version {}", version)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+#[derive(Serialize, Deserialize)]
+pub struct Solver<const N: usize, T: Pattern<N>> {
+    pub image_sp: ImageSuperposition<N, T>,
+    snapshot_stack: SnapshotStack<N, T>,
+    pub stats: RunStats,
+}
+
+impl<const N: usize, T: Pattern<N>> Solver<N, T> {
+    pub fn new(image_sp: ImageSuperposition<N, T>) -> Self {
+        Self {
+            stats: RunStats::new(&image_sp),
+            image_sp,
+            snapshot_stack: SnapshotStack::new(),
+        }
+    }
+
+    /// Extracts the ruleset of the sample and propagates it, timing both phases.
+    pub fn from_sample(sample: Image<T::Value>, width: u32, height: u32, seed: u64) -> Self {
+        let image_sp = ImageSuperposition::with_seed(width, height, seed);
+        Self::extract_and_propagate(image_sp, |image_sp| image_sp.extract(sample))
+    }
+
+    /// Like `from_sample`, but learns the 3D neighborhoods of a voxel sample and
+    /// generates a grid of `depth` layers.
+    pub fn from_voxel_sample(
+        sample: VoxelGrid<T::Value>,
+        width: u32,
+        height: u32,
+        depth: u32,
+        seed: u64,
+    ) -> Self
+    where
+        T: VoxelPattern<N>,
+    {
+        let image_sp = ImageSuperposition::with_depth(width, height, depth, seed);
+        Self::extract_and_propagate(image_sp, |image_sp| image_sp.extract_voxels(&sample))
+    }
+
+    fn extract_and_propagate<F: FnOnce(&mut ImageSuperposition<N, T>)>(
+        mut image_sp: ImageSuperposition<N, T>,
+        extract: F,
+    ) -> Self {
+        let start = Instant::now();
+        extract(&mut image_sp);
+        let extract = start.elapsed();
+
+        let start = Instant::now();
+        image_sp.propagate_all();
+        let propagate = start.elapsed();
+
+        let mut solver = Self::new(image_sp);
+        solver.stats.timings.extract = extract.as_secs_f64();
+        solver.stats.timings.propagate = propagate.as_secs_f64();
+        solver
+    }
+
+    /// Collapses the pixel with the lowest entropy and propagates the result,
+    /// backtracking to previous collapses as long as there are contradictions.
+    pub fn step(&mut self) -> Status {
+        self.step_with(&mut ())
+    }
+
+    /// Like `step`, but reports every event to the observer.
+    pub fn step_with<O: Observer<N, T>>(&mut self, observer: &mut O) -> Status {
+        let start = Instant::now();
+        let status = self.collapse_and_propagate(observer);
+        self.stats.timings.solve += start.elapsed().as_secs_f64();
+
+        if status == Status::Running {
+            observer.on_step(&self.image_sp);
+        } else {
+            self.stats.dead_pixels = self.image_sp.count_dead_pixels();
+            observer.on_finished(&self.image_sp, &self.stats);
+        }
+        status
+    }
+
+    fn collapse_and_propagate<O: Observer<N, T>>(&mut self, observer: &mut O) -> Status {
+        let Some(pixel_index) = self.image_sp.search() else {
+            // the initial propagation may already have left pixels without any color
+            if self.image_sp.count_dead_pixels() > 0 {
+                return Status::Contradiction;
+            }
+            return Status::Solved;
+        };
+
+        let mut observer = (&mut self.stats, observer);
+
+        let image_sp_clone = self.image_sp.clone();
+
+        let collapse_color_index = self.image_sp.collapse(pixel_index);
+        let color = self.image_sp.pixels[pixel_index].colors[0].color;
+        observer.on_collapse(&self.image_sp, pixel_index, color);
+
+        self.snapshot_stack.push(Snapshot {
+            image_sp: image_sp_clone,
+            collapse_pixel_index: pixel_index,
+            collapse_color_index,
+        });
+        observer.0.max_stack_depth = observer.0.max_stack_depth.max(self.snapshot_stack.len());
+
+        let mut pi = pixel_index;
+        while !self.image_sp.propagate(pi, &mut observer) {
+            let Some(snapshot) = self.snapshot_stack.pop() else {
+                return Status::Contradiction;
+            };
+            self.image_sp = snapshot.image_sp;
+            observer.on_backtrack(&self.image_sp, self.snapshot_stack.len());
+            pi = snapshot.collapse_pixel_index;
+        }
+
+        Status::Running
+    }
+
+    pub fn run(&mut self) -> Status {
+        self.run_with(&mut ())
+    }
+
+    pub fn run_with<O: Observer<N, T>>(&mut self, observer: &mut O) -> Status {
+        loop {
+            let status = self.step_with(observer);
+            if status != Status::Running {
+                return status;
+            }
+        }
+    }
+}
+
+impl<const N: usize, T: Pattern<N> + Serialize + DeserializeOwned> Solver<N, T> {
+    /// Encodes the complete state of the solver, including the rng and all snapshots
+    /// needed for backtracking, so that resuming yields the same result.
+    pub fn to_checkpoint(&self) -> Vec<u8> {
+        let mut bytes = CHECKPOINT_MAGIC.to_vec();
+        bytes.extend(CHECKPOINT_VERSION.to_le_bytes());
+        bytes.extend(bincode::serialize(self).expect("solver is serializable"));
+        bytes
+    }
+
+    pub fn from_checkpoint(bytes: &[u8]) -> Result<Self, CheckpointError> {
+        if bytes.len() < 8 || bytes[0..4] != CHECKPOINT_MAGIC {
+            return Err(CheckpointError::InvalidHeader);
+        }
+
+        let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        if version != CHECKPOINT_VERSION {
+            return Err(CheckpointError::UnsupportedVersion(version));
+        }
+
+        bincode::deserialize(&bytes[8..]).map_err(CheckpointError::Binary)
+    }
+
+    pub fn save_checkpoint<P: AsRef<Path>>(&self, path: P) -> Result<(), CheckpointError> {
+        fs::write(path, self.to_checkpoint()).map_err(CheckpointError::Io)
+    }
+
+    pub fn load_checkpoint<P: AsRef<Path>>(path: P) -> Result<Self, CheckpointError> {
+        let bytes = fs::read(path).map_err(CheckpointError::Io)?;
+        Self::from_checkpoint(&bytes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{color::Color, image::load_image, pattern8::Pattern8};
+
+    fn create_solver() -> Solver<8, Pattern8> {
+        let mut image_sp = ImageSuperposition::<8, Pattern8>::with_seed(12, 12, 1987082627);
+        image_sp.extract(load_image("./test/Water.png"));
+        image_sp.propagate_all();
+        Solver::new(image_sp)
+    }
+
+    #[test]
+    fn it_resumes_from_a_checkpoint_with_the_same_result() {
+        let mut uninterrupted = create_solver();
+        uninterrupted.run();
+
+        let mut interrupted = create_solver();
+        for _ in 0..20 {
+            interrupted.step();
+        }
+        let checkpoint = interrupted.to_checkpoint();
+        drop(interrupted);
+
+        let mut resumed = Solver::<8, Pattern8>::from_checkpoint(&checkpoint).unwrap();
+        resumed.run();
+
+        let colors = |solver: &Solver<8, Pattern8>| -> Vec<_> {
+            solver
+                .image_sp
+                .pixels
+                .iter()
+                .map(|p| p.colors[0].color)
+                .collect()
+        };
+        assert_eq!(colors(&resumed), colors(&uninterrupted));
+    }
+
+    #[test]
+    fn it_collects_the_stats_of_the_run() {
+        let mut solver =
+            Solver::<8, Pattern8>::from_sample(load_image("./test/Water.png"), 12, 12, 1987082627);
+
+        let status = solver.run();
+
+        let stats = &solver.stats;
+        assert_eq!(status, Status::Solved);
+        assert_eq!(stats.seed, 1987082627);
+        assert!(stats.colors > 1 && stats.patterns >= stats.colors);
+        assert!(stats.collapses > 0 && stats.collapses <= 144 + stats.backtracks);
+        assert!(stats.max_stack_depth > 0 && stats.max_stack_depth <= stats.collapses);
+        assert!(stats.propagation_steps > 0);
+        assert!(stats.timings.extract > 0.0 && stats.timings.solve > 0.0);
+        assert_eq!(stats.dead_pixels, 0);
+    }
+
+    #[test]
+    fn it_reports_pixels_left_without_color_by_the_first_propagation() {
+        let (b, w) = (Color(0xff000000), Color(0xffffffff));
+        let sample = Image {
+            width: 4,
+            height: 1,
+            colors: vec![b, w, b, w],
+        };
+        let mut image_sp = ImageSuperposition::<8, Pattern8>::with_seed(4, 1, 0);
+        image_sp.extract(sample);
+        // two black pixels are never next to each other in the sample
+        image_sp.fix_color_at(1, b);
+        image_sp.fix_color_at(2, b);
+        image_sp.propagate_all();
+        assert!(image_sp.count_dead_pixels() > 0);
+
+        let mut solver = Solver::new(image_sp);
+
+        assert_eq!(solver.run(), Status::Contradiction);
+        assert!(solver.stats.dead_pixels > 0);
+    }
+
+    #[test]
+    fn it_rejects_invalid_checkpoints() {
+        assert!(matches!(
+            Solver::<8, Pattern8>::from_checkpoint(b"WFCR\x01\x00\x00\x00"),
+            Err(CheckpointError::InvalidHeader)
+        ));
+    }
+}
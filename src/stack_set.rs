@@ -30,6 +30,11 @@ impl StackSet {
         }
     }
 
+    /// The values in the order they were pushed, the last one is popped first.
+    pub fn values(&self) -> &[usize] {
+        &self.stack
+    }
+
     pub fn pop(&mut self) -> Option<usize> {
         if let Some(value) = self.stack.pop() {
             self.occupied[value] = false;
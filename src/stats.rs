@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{observer::Observer, pattern::Pattern, superposition::ImageSuperposition};
+
+/// Counters of a solver run, updated from the same events the observers receive.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunStats {
+    pub seed: u64,
+    pub colors: usize,
+    pub patterns: usize,
+    pub collapses: usize,
+    pub bans: usize,
+    pub contradictions: usize,
+    pub backtracks: usize,
+    pub max_stack_depth: usize,
+    /// How often a pixel was revised during propagation.
+    pub propagation_steps: usize,
+    pub timings: Timings,
+    /// Pixels without any possible color when the run finished.
+    pub dead_pixels: usize,
+}
+
+/// Wall time of each phase in seconds.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Timings {
+    /// Extracting the ruleset from the sample.
+    pub extract: f64,
+    /// Propagating the ruleset before the first collapse.
+    pub propagate: f64,
+    /// Collapsing, propagating and backtracking until the run finished.
+    pub solve: f64,
+}
+
+impl RunStats {
+    /// Starts the stats of a run on the superposition, counting the distinct colors
+    /// and the distinct patterns of every color, as many as any pixel still allows.
+    pub fn new<const N: usize, T: Pattern<N>>(image_sp: &ImageSuperposition<N, T>) -> Self {
+        let mut patterns: HashMap<T::Value, usize> = HashMap::new();
+        for color_sp in image_sp.pixels.iter().flat_map(|pixel_sp| &pixel_sp.colors) {
+            let count = patterns.entry(color_sp.color).or_default();
+            if color_sp.patterns.len() <= *count {
+                continue;
+            }
+
+            let mut distinct: Vec<T> = Vec::new();
+            for pattern in &color_sp.patterns {
+                if !distinct.iter_mut().any(|other| other.merge(pattern)) {
+                    distinct.push(pattern.clone());
+                }
+            }
+            *count = (*count).max(distinct.len());
+        }
+
+        Self {
+            seed: image_sp.seed,
+            colors: patterns.len(),
+            patterns: patterns.values().sum(),
+            ..Default::default()
+        }
+    }
+}
+
+impl<const N: usize, T: Pattern<N>> Observer<N, T> for RunStats {
+    fn on_collapse(&mut self, _: &ImageSuperposition<N, T>, _: usize, _: T::Value) {
+        self.collapses += 1;
+    }
+
+    fn on_propagate(&mut self, _: &ImageSuperposition<N, T>, _: usize) {
+        self.propagation_steps += 1;
+    }
+
+    fn on_ban(&mut self, _: &ImageSuperposition<N, T>, _: usize, _: T::Value) {
+        self.bans += 1;
+    }
+
+    fn on_contradiction(&mut self, _: &ImageSuperposition<N, T>, _: usize) {
+        self.contradictions += 1;
+    }
+
+    fn on_backtrack(&mut self, _: &ImageSuperposition<N, T>, _: usize) {
+        self.backtracks += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{color::Color, image::Image, pattern8::Pattern8, superposition::Wfc};
+
+    #[test]
+    fn it_counts_the_colors_and_patterns_of_the_ruleset() {
+        let (b, w) = (Color(0xff000000), Color(0xffffffff));
+        let sample = Image {
+            width: 3,
+            height: 1,
+            colors: vec![b, w, b],
+        };
+        let mut image_sp = ImageSuperposition::<8, Pattern8>::with_seed(4, 4, 7);
+        image_sp.extract(sample);
+
+        let stats = RunStats::new(&image_sp);
+
+        assert_eq!(stats.seed, 7);
+        assert_eq!(stats.colors, 2);
+        assert_eq!(stats.patterns, 3);
+    }
+
+    #[test]
+    fn it_counts_repeated_patterns_once() {
+        let (b, w) = (Color(0xff000000), Color(0xffffffff));
+        // the windows around the inner pixels repeat
+        let sample = Image {
+            width: 7,
+            height: 1,
+            colors: vec![b, w, b, w, b, w, b],
+        };
+        let mut image_sp = ImageSuperposition::<8, Pattern8>::with_seed(4, 4, 7);
+        image_sp.extract(sample);
+        for color_sp in image_sp.pixels.iter_mut().flat_map(|p| &mut p.colors) {
+            let patterns = color_sp.patterns.clone();
+            color_sp.patterns.extend(patterns);
+        }
+
+        let stats = RunStats::new(&image_sp);
+
+        assert_eq!(stats.colors, 2);
+        // black at either end and between two whites, white between two blacks
+        assert_eq!(stats.patterns, 4);
+    }
+}
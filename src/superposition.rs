@@ -1,37 +1,76 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 
 use oorandom::Rand32;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    color::Color, image::Image, pattern::Pattern, pattern8::Pattern8, stack_set::StackSet,
-    vec2::Vec2, weighted::Weighted,
+    color::Color,
+    image::Image,
+    observer::Observer,
+    pattern::{Pattern, VoxelPattern},
+    ruleset::Ruleset,
+    stack_set::StackSet,
+    voxel::VoxelGrid,
+    weighted::Weighted,
 };
 
-#[derive(Clone, Debug)]
+/// Pixels per task when revising the pending pixels in parallel.
+const PARALLEL_MIN_LEN: usize = 64;
+
+/// Blue, painted for pixels with more than one color left.
+pub const UNDECIDED: Color = Color(0xffff0000);
+/// Black, painted for pixels without any color left.
+pub const CONTRADICTION: Color = Color(0xff000000);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ImageSuperposition<const N: usize, T: Pattern<N>> {
     pub width: u32,
     pub height: u32,
+    /// The number of layers, 1 for images and more for voxel grids.
+    pub depth: u32,
     pub pixels: Vec<PixelSuperposition<N, T>>,
+    /// The seed the rng was created with.
+    pub seed: u64,
+    #[serde(with = "rng_state")]
     pub rng: Rand32,
+    /// Treat everything outside of the image as unknown instead of as the sample border.
+    pub open_border: bool,
+    /// Revise the pixels waiting for propagation concurrently, which pays off for
+    /// large images. Off by default.
+    pub parallel: bool,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PixelSuperposition<const N: usize, T: Pattern<N>> {
     pub colors: Vec<ColorSuperposition<N, T>>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ColorSuperposition<const N: usize, T: Pattern<N>> {
-    pub color: Color,
+    pub color: T::Value,
     pub patterns: Vec<T>,
     pub weight: usize,
 }
 
-pub trait Wfc {
-    fn extract(&mut self, image: Image);
+mod rng_state {
+    use oorandom::Rand32;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(rng: &Rand32, serializer: S) -> Result<S::Ok, S::Error> {
+        rng.state().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Rand32, D::Error> {
+        <(u64, u64)>::deserialize(deserializer).map(Rand32::from_state)
+    }
+}
+
+pub trait Wfc<const N: usize, T: Pattern<N>> {
+    fn extract(&mut self, image: Image<T::Value>);
     fn search(&self) -> Option<usize>;
     fn collapse(&mut self, pixel_index: usize) -> usize;
-    fn propagate(&mut self, pixel_index: usize) -> bool;
+    fn propagate<O: Observer<N, T>>(&mut self, pixel_index: usize, observer: &mut O) -> bool;
 }
 
 impl<const N: usize, T: Pattern<N>> Weighted for PixelSuperposition<N, T> {
@@ -40,70 +79,17 @@ impl<const N: usize, T: Pattern<N>> Weighted for PixelSuperposition<N, T> {
     }
 }
 
-impl<const N: usize, T: Pattern<N>> From<&ImageSuperposition<N, T>> for Image {
+/// Undecided pixels are blue and contradictions black.
+impl<const N: usize, T: Pattern<N, Value = Color>> From<&ImageSuperposition<N, T>> for Image {
     fn from(image_sp: &ImageSuperposition<N, T>) -> Self {
-        let mut colors = Vec::new();
-
-        let mut wrong_pixels = 0;
-        for i in 0..image_sp.pixels.len() {
-            // TODO: remove fallback
-            if image_sp.pixels[i].colors.len() == 0 {
-                colors.push(Color(0xff000000));
-                wrong_pixels += 1;
-            } else if image_sp.pixels[i].colors.len() > 1 {
-                colors.push(Color(0xffff0000));
-            } else {
-                colors.push(image_sp.pixels[i].colors[0].color);
-            }
-        }
-
-        println!("save image, dead pixels: {}", wrong_pixels);
-
-        Image {
-            width: image_sp.width,
-            height: image_sp.height,
-            colors,
-        }
+        image_sp.to_image(UNDECIDED, CONTRADICTION)
     }
 }
 
-impl Wfc for ImageSuperposition<8, Pattern8> {
-    fn extract(&mut self, image: Image) {
-        // TODO: pixels at the borders have lower entropy: reduce possibilities
-        let mut pixel_sp = PixelSuperposition { colors: Vec::new() };
-
-        for y in 0..image.height as i32 {
-            for x in 0..image.width as i32 {
-                let color = image
-                    .get_color_at(Vec2 { x, y })
-                    .expect("image index not allowed");
-
-                let color_index = get_color_index(color, &pixel_sp);
-                let pattern = Pattern8::extract_pattern_at(&image, Vec2 { x, y });
-
-                match color_index {
-                    Some(color_index) => {
-                        pixel_sp.colors[color_index].patterns.push(pattern);
-                        pixel_sp.colors[color_index].weight =
-                            pixel_sp.colors[color_index].patterns.len();
-                    }
-                    None => pixel_sp.colors.push(ColorSuperposition {
-                        color,
-                        patterns: vec![pattern],
-                        weight: 1,
-                    }),
-                }
-            }
-        }
-
-        self.pixels = vec![pixel_sp; (self.width * self.height) as usize];
-
-        //ImageSuperposition {
-        //    width: image.width,
-        //    height: image.height,
-        //    pixels: vec![pixel_sp; (image.width * image.height) as usize],
-        //    rng: Rand32::new(1987082611),
-        //}
+impl<const N: usize, T: Pattern<N>> Wfc<N, T> for ImageSuperposition<N, T> {
+    fn extract(&mut self, image: Image<T::Value>) {
+        let ruleset = Ruleset::extract(&image);
+        self.apply_ruleset(&ruleset);
     }
 
     fn search(&self) -> Option<usize> {
@@ -112,7 +98,7 @@ impl Wfc for ImageSuperposition<8, Pattern8> {
 
         for i in 0..self.pixels.len() {
             let pixel_sp = &self.pixels[i];
-            if is_collapsed(&pixel_sp) {
+            if is_collapsed(pixel_sp) {
                 continue;
             }
 
@@ -128,17 +114,13 @@ impl Wfc for ImageSuperposition<8, Pattern8> {
     }
 
     fn collapse(&mut self, pixel_index: usize) -> usize {
-        println!(
-            "collapse at: {:?}",
-            Vec2::from_index(pixel_index, self.width)
-        );
         //println!("  `-> {:?}", self.pixels[pixel_index]);
         //let pixel_sp = &self.pixels[pixel_index];
 
         // TODO: improve or remove?
         for i in 0..self.pixels[pixel_index].colors.len() {
             self.pixels[pixel_index].colors[i].weight =
-                self.pixels[pixel_index].colors[i].patterns.len();
+                calc_color_weight(&self.pixels[pixel_index].colors[i]);
         }
 
         let color_index = self.pixels[pixel_index]
@@ -153,105 +135,238 @@ impl Wfc for ImageSuperposition<8, Pattern8> {
         color_index
     }
 
-    fn propagate(&mut self, pixel_index: usize) -> bool {
+    fn propagate<O: Observer<N, T>>(&mut self, pixel_index: usize, observer: &mut O) -> bool {
         let mut indices = StackSet::new(self.pixels.len()); // TODO: performance, make struct member?
-        Pattern8::add_neighbors(&mut indices, pixel_index, self.width, self.height); // TODO: is reference to Pattern8 necessary?
+        T::add_neighbors(
+            &mut indices,
+            pixel_index,
+            self.width,
+            self.height,
+            self.depth,
+        );
+
+        if self.parallel {
+            self.propagate_speculatively(indices, observer, true)
+        } else {
+            self.propagate_sequentially(indices, observer, true)
+        }
+    }
+}
+
+impl<const N: usize, T: Pattern<N>> ImageSuperposition<N, T> {
+    pub fn propagate_all(&mut self) {
+        let indices = StackSet::full(self.pixels.len());
+        if self.parallel {
+            self.propagate_speculatively(indices, &mut (), false);
+        } else {
+            self.propagate_sequentially(indices, &mut (), false);
+        }
+    }
 
+    /// Narrows one pixel after the other until no pixel changes anymore. Collapsed
+    /// pixels are narrowed as well: their color is decided, but not which of its
+    /// patterns, and a neighbor may only agree with some of them.
+    fn propagate_sequentially<O: Observer<N, T>>(
+        &mut self,
+        mut indices: StackSet,
+        observer: &mut O,
+        stop_at_contradiction: bool,
+    ) -> bool {
         while let Some(pixel_index) = indices.pop() {
-            if !self.is_collapsed_at(pixel_index) {
-                if self.collapse_partially(pixel_index) {
-                    if self.pixels[pixel_index].colors.len() == 0 {
-                        return false;
-                    }
-                    Pattern8::add_neighbors(&mut indices, pixel_index, self.width, self.height); // TODO: is reference to Pattern8 necessary?
+            let revision = self.revise(pixel_index);
+            let has_changed = self.apply_revision(pixel_index, revision, observer);
+            observer.on_propagate(self, pixel_index);
+            if has_changed {
+                if stop_at_contradiction && self.pixels[pixel_index].colors.is_empty() {
+                    observer.on_contradiction(self, pixel_index);
+                    return false;
                 }
+                T::add_neighbors(
+                    &mut indices,
+                    pixel_index,
+                    self.width,
+                    self.height,
+                    self.depth,
+                );
             }
         }
 
         true
     }
-}
 
-impl<const N: usize, T: Pattern<N>> ImageSuperposition<N, T> {
-    // TODO: DRY
-    pub fn propagate_all(&mut self) {
-        let mut indices = StackSet::full(self.pixels.len());
+    /// Like `propagate_sequentially`, with the same result and events, but revises
+    /// all pixels waiting on the stack concurrently whenever it runs out of revisions.
+    /// A revision only depends on the pixel and its neighbors, so it stays valid
+    /// until one of them changes.
+    fn propagate_speculatively<O: Observer<N, T>>(
+        &mut self,
+        mut indices: StackSet,
+        observer: &mut O,
+        stop_at_contradiction: bool,
+    ) -> bool {
+        let mut revisions: HashMap<usize, Revision<N, T>> = HashMap::new();
         while let Some(pixel_index) = indices.pop() {
-            if !self.is_collapsed_at(pixel_index) {
-                if self.collapse_partially(pixel_index) {
-                    Pattern8::add_neighbors(&mut indices, pixel_index, self.width, self.height); // TODO: is reference to Pattern8 necessary?
+            let revision = match revisions.remove(&pixel_index) {
+                Some(revision) => revision,
+                None => {
+                    if revisions.is_empty() {
+                        revisions = indices
+                            .values()
+                            .par_iter()
+                            .with_min_len(PARALLEL_MIN_LEN)
+                            .map(|&i| (i, self.revise(i)))
+                            .collect();
+                    }
+                    self.revise(pixel_index)
+                }
+            };
+            let has_changed = self.apply_revision(pixel_index, revision, observer);
+            observer.on_propagate(self, pixel_index);
+            if has_changed {
+                if stop_at_contradiction && self.pixels[pixel_index].colors.is_empty() {
+                    observer.on_contradiction(self, pixel_index);
+                    return false;
                 }
+                revisions.remove(&pixel_index);
+                for neighbor in T::get_neighbors(pixel_index, self.width, self.height, self.depth) {
+                    revisions.remove(&neighbor);
+                }
+                T::add_neighbors(
+                    &mut indices,
+                    pixel_index,
+                    self.width,
+                    self.height,
+                    self.depth,
+                );
             }
         }
+
+        true
     }
 
     pub fn new(width: u32, height: u32) -> Self {
-        let millis: u64 = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
-        //let millis = 1746367627610;
-        println!("seed: {}", millis);
+        let seed = time_seed();
+        log::info!("seed: {}", seed);
+        Self::with_seed(width, height, seed)
+    }
+
+    pub fn with_seed(width: u32, height: u32, seed: u64) -> Self {
+        Self::with_depth(width, height, 1, seed)
+    }
+
+    /// A superposition of `depth` layers, for patterns with neighbors in 3D.
+    pub fn with_depth(width: u32, height: u32, depth: u32, seed: u64) -> Self {
         Self {
             width,
             height,
+            depth,
             pixels: Vec::new(),
-            //rng: Rand32::new(1987082627),
-            rng: Rand32::new(millis),
+            seed,
+            rng: Rand32::new(seed),
+            open_border: false,
+            parallel: false,
         }
     }
 
-    fn is_collapsed_at(&self, pixel_index: usize) -> bool {
-        let pixel_sp = &self.pixels[pixel_index];
-        is_collapsed(pixel_sp)
+    /// Restarts the rng, as if the superposition was created with this seed.
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = Rand32::new(seed);
     }
 
-    fn get_collapsed_color_at(&self, pixel_index: usize) -> Option<Color> {
-        let pixel_sp = &self.pixels[pixel_index];
-        if is_collapsed(pixel_sp) {
-            // TODO: this is bad
-            if pixel_sp.colors.len() == 0 {
-                Some(Color(0))
-            } else {
-                Some(pixel_sp.colors[0].color)
-            }
-        } else {
-            None
+    /// Resets every pixel to all colors and patterns of the ruleset.
+    pub fn apply_ruleset(&mut self, ruleset: &Ruleset<N, T>) {
+        let pixel_sp = PixelSuperposition {
+            colors: ruleset.colors.clone(),
+        };
+        self.pixels = vec![pixel_sp; (self.width * self.height * self.depth) as usize];
+    }
+
+    /// Like `extract`, but learns the 3D neighborhoods of a voxel sample.
+    pub fn extract_voxels(&mut self, grid: &VoxelGrid<T::Value>)
+    where
+        T: VoxelPattern<N>,
+    {
+        let ruleset = Ruleset::extract_voxels(grid);
+        self.apply_ruleset(&ruleset);
+    }
+
+    /// The color of every collapsed pixel, with placeholders for the undecided pixels
+    /// and those without any color. The layers of a voxel grid are stacked vertically.
+    pub fn to_image(&self, undecided: T::Value, contradiction: T::Value) -> Image<T::Value> {
+        let colors = self
+            .pixels
+            .iter()
+            .map(|pixel_sp| match pixel_sp.colors.len() {
+                0 => contradiction,
+                1 => pixel_sp.colors[0].color,
+                _ => undecided,
+            })
+            .collect();
+
+        Image {
+            width: self.width,
+            height: self.height * self.depth,
+            colors,
         }
     }
 
+    /// Like `to_image`, but keeps the layers of a voxel grid apart.
+    pub fn to_voxels(&self, undecided: T::Value, contradiction: T::Value) -> VoxelGrid<T::Value> {
+        let image = self.to_image(undecided, contradiction);
+        VoxelGrid {
+            width: self.width,
+            height: self.height,
+            depth: self.depth,
+            voxels: image.colors,
+        }
+    }
+
+    /// Restricts the pixel to a single color, keeping all of its patterns.
+    /// Returns false if the color is not possible at this pixel.
+    pub fn fix_color_at(&mut self, pixel_index: usize, color: T::Value) -> bool {
+        self.pixels[pixel_index]
+            .colors
+            .retain(|color_sp| color_sp.color == color);
+        !self.pixels[pixel_index].colors.is_empty()
+    }
+
+    /// Counts the pixels without any possible color.
+    pub fn count_dead_pixels(&self) -> usize {
+        self.pixels
+            .iter()
+            .filter(|pixel_sp| pixel_sp.colors.is_empty())
+            .count()
+    }
+
     //fn get_colors_at(&self, pixel_index: usize) -> &Vec<Color> {
     //    &self.pixels[pixel_index].colors.
     //}
 
-    fn collapse_partially(&mut self, pixel_index: usize) -> bool {
-        // TODO: better collapse: also take into account non fully collapsed pixels
-
+    /// Narrows the colors and patterns of the pixel to those its neighbors allow.
+    fn revise(&self, pixel_index: usize) -> Revision<N, T> {
         let mut has_changed = false;
+        let neighbors = T::get_neighbors_opt(pixel_index, self.width, self.height, self.depth);
 
         // build new colors for the current pixel
         let mut new_colors = Vec::new();
+        let mut banned_colors = Vec::new();
         for k in 0..self.pixels[pixel_index].colors.len() {
             let color1 = self.pixels[pixel_index].colors[k].color; // TODO: rename, without naming collision...
 
             // check if current color is even possible by all surrounding patterns and skip if not
-            let neighbors = Pattern8::get_neighbors_opt(pixel_index, self.width, self.height);
             let mut skip_color = false;
-            assert!(neighbors.len() == 8);
-            for r in 0..neighbors.len() {
-                if let Some(neighbor_index) = neighbors[r] {
-                    let mut color_set = HashSet::new();
-                    for s in 0..self.pixels[neighbor_index].colors.len() {
-                        for t in 0..self.pixels[neighbor_index].colors[s].patterns.len() {
-                            let pattern = &self.pixels[neighbor_index].colors[s].patterns[t];
-                            if let Some(color_reverse) = pattern.get_color_at(7 - r) {
-                                // TODO: 7?
-                                color_set.insert(color_reverse);
-                            }
-                        }
-                    }
-
-                    if !color_set.contains(&color1) {
+            for (r, neighbor) in neighbors.iter().enumerate() {
+                if let Some(neighbor_index) = *neighbor {
+                    let reverse_index = T::get_reverse_index(r);
+                    let is_possible = self.pixels[neighbor_index].colors.iter().any(|color_sp| {
+                        color_sp
+                            .patterns
+                            .iter()
+                            .any(|pattern| pattern.matches(reverse_index, Some(color1)))
+                    });
+
+                    if !is_possible {
                         skip_color = true;
                         break;
                     }
@@ -259,6 +374,7 @@ impl<const N: usize, T: Pattern<N>> ImageSuperposition<N, T> {
             }
             if skip_color {
                 has_changed = true;
+                banned_colors.push(color1);
                 continue;
             }
 
@@ -266,50 +382,26 @@ impl<const N: usize, T: Pattern<N>> ImageSuperposition<N, T> {
             let mut new_patterns = Vec::new();
             for j in 0..self.pixels[pixel_index].colors[k].patterns.len() {
                 let pattern = &self.pixels[pixel_index].colors[k].patterns[j];
-                let neighbors_and_colors =
-                    pattern.get_neighbors_and_colors(pixel_index, self.width, self.height);
 
                 // TODO: outsource
                 let mut pattern_conforms = true;
-                for i in 0..neighbors_and_colors.len() {
-                    let (neighbor_index, pattern_color) = neighbors_and_colors[i];
-
-                    if neighbor_index.is_none() && pattern_color.is_none() {
-                        continue;
-                    }
-
-                    if neighbor_index.is_none() && pattern_color.is_some() {
-                        pattern_conforms = false;
-                        break;
-                    }
-
-                    if neighbor_index.is_some() && pattern_color.is_none() {
-                        pattern_conforms = false;
-                        break;
-                    }
-
-                    // TODO: replace by: get all possible colors there, get all possible pattern colors here ..
-                    // for each pattern position (8-neighborhood)
-                    //   get list of possible colors there (other pixel)
-                    //   keep only those patterns, that conform
-                    //if let Some(neighbor_color) =
-                    //    self.get_collapsed_color_at(neighbor_index.unwrap())
-                    //{
-                    //    if Some(neighbor_color) != pattern_color {
-                    //        pattern_conforms = false;
-                    //        break;
-                    //    }
-                    //}
-
-                    // note that both options are "Some" at this point
-                    let mut is_any_match = false;
-                    for ci in 0..self.pixels[neighbor_index.unwrap()].colors.len() {
-                        let color = self.pixels[neighbor_index.unwrap()].colors[ci].color;
-                        if color == pattern_color.unwrap() {
-                            is_any_match = true;
+                for (i, neighbor) in neighbors.iter().enumerate() {
+                    let Some(neighbor_index) = *neighbor else {
+                        if !self.open_border && !pattern.matches(i, None) {
+                            pattern_conforms = false;
                             break;
                         }
-                    }
+                        continue;
+                    };
+
+                    // the neighbor needs a color and a pattern which agree with this one
+                    let is_any_match = self.pixels[neighbor_index].colors.iter().any(|color_sp| {
+                        pattern.matches(i, Some(color_sp.color))
+                            && color_sp
+                                .patterns
+                                .iter()
+                                .any(|other| pattern.agrees(color1, i, other, color_sp.color))
+                    });
                     if !is_any_match {
                         pattern_conforms = false;
                         break;
@@ -323,34 +415,71 @@ impl<const N: usize, T: Pattern<N>> ImageSuperposition<N, T> {
                 }
             }
 
-            if new_patterns.len() > 0 {
+            if !new_patterns.is_empty() {
                 new_colors.push(ColorSuperposition {
                     color: self.pixels[pixel_index].colors[k].color,
                     patterns: new_patterns,
                     weight: self.pixels[pixel_index].colors[k].weight,
                 });
+            } else {
+                banned_colors.push(color1);
             }
         }
 
-        self.pixels[pixel_index].colors = new_colors;
+        Revision {
+            colors: new_colors,
+            banned_colors,
+            has_changed,
+        }
+    }
+
+    fn apply_revision<O: Observer<N, T>>(
+        &mut self,
+        pixel_index: usize,
+        revision: Revision<N, T>,
+        observer: &mut O,
+    ) -> bool {
+        if !revision.has_changed {
+            return false;
+        }
 
-        has_changed
+        self.pixels[pixel_index].colors = revision.colors;
+        for color in revision.banned_colors {
+            observer.on_ban(self, pixel_index, color);
+        }
+
+        true
     }
 }
 
+/// The colors of a pixel after narrowing them to its neighbors.
+struct Revision<const N: usize, T: Pattern<N>> {
+    colors: Vec<ColorSuperposition<N, T>>,
+    banned_colors: Vec<T::Value>,
+    has_changed: bool,
+}
+
+/// Milliseconds since the unix epoch, for runs without a fixed seed.
+pub fn time_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
 // TODO: impl PixelSuperposition
 fn is_collapsed<const N: usize, T: Pattern<N>>(pixel_sp: &PixelSuperposition<N, T>) -> bool {
     pixel_sp.colors.len() <= 1
 }
 
-// TODO: impl PixelSuperposition
-fn calc_weights<const N: usize, T: Pattern<N>>(pixel_sp: &PixelSuperposition<N, T>) -> usize {
-    let mut total_weight = 0;
-    for i in 0..pixel_sp.colors.len() {
-        let color = &pixel_sp.colors[i];
-        total_weight += color.patterns.len();
-    }
-    total_weight
+pub(crate) fn calc_color_weight<const N: usize, T: Pattern<N>>(
+    color_sp: &ColorSuperposition<N, T>,
+) -> usize {
+    color_sp
+        .patterns
+        .iter()
+        .map(|pattern| pattern.weight())
+        .sum()
 }
 
 // TODO: impl PixelSuperposition
@@ -358,19 +487,21 @@ fn calc_total_weight<const N: usize, T: Pattern<N>>(pixel_sp: &PixelSuperpositio
     let mut total_weight = 0;
     for i in 0..pixel_sp.colors.len() {
         let color = &pixel_sp.colors[i];
-        total_weight += color.patterns.len();
+        total_weight += calc_color_weight(color);
     }
     total_weight
 }
 
 // TODO: impl PixelSuperposition
-fn calc_entropy<const N: usize, T: Pattern<N>>(pixel_sp: &PixelSuperposition<N, T>) -> f32 {
-    let total_weight = calc_total_weight(&pixel_sp);
+pub(crate) fn calc_entropy<const N: usize, T: Pattern<N>>(
+    pixel_sp: &PixelSuperposition<N, T>,
+) -> f32 {
+    let total_weight = calc_total_weight(pixel_sp);
 
     let mut entropy = 0.0;
     for i in 0..pixel_sp.colors.len() {
         let color = &pixel_sp.colors[i];
-        let color_weight = color.patterns.len();
+        let color_weight = calc_color_weight(color);
         let color_probability = color_weight as f32 / total_weight as f32;
 
         entropy += color_probability * color_probability.ln();
@@ -379,23 +510,10 @@ fn calc_entropy<const N: usize, T: Pattern<N>>(pixel_sp: &PixelSuperposition<N,
     -entropy
 }
 
-// TODO: impl PixelSuperposition
-fn get_color_index<const N: usize, T: Pattern<N>>(
-    color: Color,
-    pixel_sp: &PixelSuperposition<N, T>,
-) -> Option<usize> {
-    for i in 0..pixel_sp.colors.len() {
-        let c = pixel_sp.colors[i].color;
-        if c == color {
-            return Some(i);
-        }
-    }
-    None
-}
-
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::pattern8::Pattern8;
 
     #[test]
     fn it_extracts_1_pattern_from_a_simple_image() {
@@ -455,4 +573,95 @@ mod test {
                 + 5.0 / total * (5.0 / total).ln())
         );
     }
+
+    #[test]
+    fn it_narrows_the_patterns_of_collapsed_pixels() {
+        let (a, b, c) = (Color(0xff000000), Color(0xff808080), Color(0xffffffff));
+        // b is either between a and c or between c and a
+        let sample = Image {
+            width: 5,
+            height: 1,
+            colors: vec![a, b, c, b, a],
+        };
+        let mut image_sp = ImageSuperposition::<8, Pattern8>::with_seed(3, 1, 1);
+        image_sp.open_border = true;
+        image_sp.extract(sample);
+
+        image_sp.fix_color_at(0, a);
+        image_sp.fix_color_at(1, b);
+        image_sp.propagate_all();
+
+        let colors: Vec<_> = image_sp.pixels[2].colors.iter().map(|c| c.color).collect();
+        assert_eq!(colors, vec![c]);
+    }
+
+    #[test]
+    fn it_propagates_in_parallel_with_the_same_result() {
+        use crate::{image::load_image, solver::Solver};
+
+        let sample = load_image("./test/Water.png");
+        for seed in 0..10 {
+            let solve = |parallel: bool| {
+                let mut image_sp = ImageSuperposition::<8, Pattern8>::with_seed(12, 12, seed);
+                image_sp.parallel = parallel;
+                image_sp.extract(sample.clone());
+                image_sp.propagate_all();
+                let mut solver = Solver::new(image_sp);
+                let status = solver.run();
+                (status, solver.image_sp.pixels)
+            };
+
+            let (sequential_status, sequential) = solve(false);
+            let (parallel_status, parallel) = solve(true);
+
+            assert_eq!(parallel_status, sequential_status, "seed {}", seed);
+            for (p, s) in parallel.iter().zip(&sequential) {
+                assert_eq!(p.colors, s.colors);
+            }
+        }
+    }
+
+    #[test]
+    fn it_propagates_large_grids_speculatively_like_sequentially() {
+        use crate::image::load_image;
+
+        /// Records the order in which the pixels are revised.
+        struct Revised(Vec<usize>);
+
+        impl Observer<8, Pattern8> for Revised {
+            fn on_propagate(&mut self, _: &ImageSuperposition<8, Pattern8>, pixel_index: usize) {
+                self.0.push(pixel_index);
+            }
+        }
+
+        // many times PARALLEL_MIN_LEN pixels, so the revisions are split up
+        let mut image_sp = ImageSuperposition::<8, Pattern8>::with_seed(64, 64, 1);
+        image_sp.extract(load_image("./test/Water.png"));
+        let color = image_sp.pixels[0].colors[0].color;
+        let fixed: Vec<usize> = (0..image_sp.pixels.len()).step_by(97).collect();
+        for &i in &fixed {
+            image_sp.fix_color_at(i, color);
+        }
+
+        let propagate = |parallel: bool| {
+            let mut image_sp = image_sp.clone();
+            let mut revised = Revised(Vec::new());
+            let indices = StackSet::full(image_sp.pixels.len());
+            let is_consistent = if parallel {
+                image_sp.propagate_speculatively(indices, &mut revised, false)
+            } else {
+                image_sp.propagate_sequentially(indices, &mut revised, false)
+            };
+            (is_consistent, image_sp.pixels, revised.0)
+        };
+        let (sequential_result, sequential, sequential_order) = propagate(false);
+        let (speculative_result, speculative, speculative_order) = propagate(true);
+
+        assert_eq!(speculative_result, sequential_result);
+        assert!(sequential_order.len() > image_sp.pixels.len());
+        assert_eq!(speculative_order, sequential_order);
+        for (p, s) in speculative.iter().zip(&sequential) {
+            assert_eq!(p.colors, s.colors);
+        }
+    }
 }
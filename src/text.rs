@@ -0,0 +1,143 @@
+use std::{fmt, fs, path::Path};
+
+use crate::{image::Image, pattern::Pattern, superposition::ImageSuperposition};
+
+/// Written for cells which are still undecided.
+pub const UNDECIDED: char = '?';
+/// Written for cells without any possible char.
+pub const CONTRADICTION: char = '!';
+
+#[derive(Debug)]
+pub enum TextError {
+    Io(std::io::Error),
+    Empty,
+    /// A line is longer or shorter than the first one, counting from 1.
+    RaggedLine {
+        line: usize,
+        width: usize,
+        expected: usize,
+    },
+}
+
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextError::Io(err) => write!(f, "cannot access text map: {}", err),
+            TextError::Empty => write!(f, "text map is empty"),
+            TextError::RaggedLine {
+                line,
+                width,
+                expected,
+            } => write!(
+                f,
+                "line {} has {} chars, expected {} like the first line",
+                line, width, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TextError {}
+
+/// Reads the text as a grid with one cell per char, e.g. `#` for walls and `.` for
+/// floors. All lines need the same number of chars, a trailing newline is ignored.
+pub fn parse_text(text: &str) -> Result<Image<char>, TextError> {
+    let lines: Vec<Vec<char>> = text
+        .lines()
+        .map(|line| line.strip_suffix('\r').unwrap_or(line).chars().collect())
+        .collect();
+
+    let width = lines.first().map_or(0, |line| line.len());
+    if width == 0 {
+        return Err(TextError::Empty);
+    }
+    if let Some(i) = lines.iter().position(|line| line.len() != width) {
+        return Err(TextError::RaggedLine {
+            line: i + 1,
+            width: lines[i].len(),
+            expected: width,
+        });
+    }
+
+    Ok(Image {
+        width: width as u32,
+        height: lines.len() as u32,
+        colors: lines.concat(),
+    })
+}
+
+/// Writes one line per row, each ending with a newline.
+pub fn to_text(image: &Image<char>) -> String {
+    let mut text = String::with_capacity(image.colors.len() + image.height as usize);
+    for row in image.colors.chunks(image.width as usize) {
+        text.extend(row);
+        text.push('\n');
+    }
+    text
+}
+
+pub fn load_text<P: AsRef<Path>>(path: P) -> Result<Image<char>, TextError> {
+    let text = fs::read_to_string(path).map_err(TextError::Io)?;
+    parse_text(&text)
+}
+
+pub fn save_text<P: AsRef<Path>>(image: &Image<char>, path: P) -> Result<(), TextError> {
+    fs::write(path, to_text(image)).map_err(TextError::Io)
+}
+
+/// The char of every collapsed cell, `UNDECIDED` and `CONTRADICTION` otherwise.
+pub fn render_text<const N: usize, T: Pattern<N, Value = char>>(
+    image_sp: &ImageSuperposition<N, T>,
+) -> Image<char> {
+    image_sp.to_image(UNDECIDED, CONTRADICTION)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        pattern8::Pattern8,
+        ruleset::Ruleset,
+        solver::{Solver, Status},
+        verify::verify,
+    };
+
+    const DUNGEON: &str = "\
+#####
+#...#
+#...#
+#####
+";
+
+    #[test]
+    fn it_parses_and_writes_text_maps() {
+        let image = parse_text("#.\r\n.#\n").unwrap();
+
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(image.colors, vec!['#', '.', '.', '#']);
+        assert_eq!(to_text(&image), "#.\n.#\n");
+        assert!(matches!(parse_text(""), Err(TextError::Empty)));
+        assert!(matches!(
+            parse_text("##\n#\n"),
+            Err(TextError::RaggedLine {
+                line: 2,
+                width: 1,
+                expected: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn it_generates_text_maps_with_the_same_solver() {
+        let sample = parse_text(DUNGEON).unwrap();
+        let ruleset = Ruleset::<8, Pattern8<char>>::extract(&sample);
+        let mut solver = Solver::<8, Pattern8<char>>::from_sample(sample, 11, 4, 3);
+
+        let status = solver.run();
+        let output = render_text(&solver.image_sp);
+
+        assert_eq!(status, Status::Solved);
+        assert!(output.colors.iter().all(|&c| c == '#' || c == '.'));
+        assert_eq!(verify(&ruleset, &output, false), vec![]);
+    }
+}
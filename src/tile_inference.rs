@@ -0,0 +1,320 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use crate::{
+    color::Color,
+    image::Image,
+    tile_set::TileSet,
+    tiled::{Adjacency, Symmetry, Tile},
+    vec2::Vec2,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum InferenceError {
+    ZeroTileSize,
+    PartialTiles {
+        width: u32,
+        height: u32,
+        tile_size: u32,
+    },
+}
+
+impl fmt::Display for InferenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InferenceError::ZeroTileSize => write!(f, "tiles must be at least 1x1"),
+            InferenceError::PartialTiles {
+                width,
+                height,
+                tile_size,
+            } => write!(
+                f,
+                "sample of {}x{} must consist of whole {}x{} tiles",
+                width, height, tile_size, tile_size
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InferenceError {}
+
+/// Slices the sample into tiles of `tile_size` x `tile_size` pixels and allows exactly
+/// those neighbors which are adjacent somewhere in the sample, in the same direction.
+///
+/// Every distinct tile becomes a tile with `X` symmetry, named `tile0`, `tile1` and so
+/// on, and weighted by how often it occurs. Rotated or mirrored tiles are distinct
+/// tiles, so the output only ever shows tiles the way the sample does.
+pub fn infer_tile_set(sample: &Image, tile_size: u32) -> Result<TileSet, InferenceError> {
+    if tile_size == 0 {
+        return Err(InferenceError::ZeroTileSize);
+    }
+    if !sample.width.is_multiple_of(tile_size) || !sample.height.is_multiple_of(tile_size) {
+        return Err(InferenceError::PartialTiles {
+            width: sample.width,
+            height: sample.height,
+            tile_size,
+        });
+    }
+
+    let columns = sample.width / tile_size;
+    let rows = sample.height / tile_size;
+    let size = tile_size as i32;
+
+    let mut tiles: Vec<Tile> = Vec::new();
+    let mut tile_indices: HashMap<Vec<Color>, usize> = HashMap::new();
+    // the tile of every cell, row by row
+    let mut tile_map: Vec<usize> = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let mut colors = Vec::with_capacity((tile_size * tile_size) as usize);
+            for y in 0..size {
+                for x in 0..size {
+                    let pos = Vec2 {
+                        x: column as i32 * size + x,
+                        y: row as i32 * size + y,
+                    };
+                    colors.push(sample.get_color_at(pos).unwrap());
+                }
+            }
+
+            let index = *tile_indices.entry(colors).or_insert_with_key(|colors| {
+                tiles.push(Tile {
+                    name: format!("tile{}", tiles.len()),
+                    image: Image {
+                        width: tile_size,
+                        height: tile_size,
+                        colors: colors.clone(),
+                    },
+                    symmetry: Symmetry::X,
+                    weight: 0,
+                });
+                tiles.len() - 1
+            });
+            tiles[index].weight += 1;
+            tile_map.push(index);
+        }
+    }
+
+    let mut adjacencies = Vec::new();
+    let mut observed = HashSet::new();
+    for (i, &first) in tile_map.iter().enumerate() {
+        let pos = Vec2::from_index(i, columns);
+        let mut neighbors = Vec::new();
+        if pos.x + 1 < columns as i32 {
+            neighbors.push((tile_map[i + 1], false));
+        }
+        if pos.y + 1 < rows as i32 {
+            neighbors.push((tile_map[i + columns as usize], true));
+        }
+
+        for (second, vertical) in neighbors {
+            let adjacency = Adjacency {
+                first,
+                first_variant: 0,
+                second,
+                second_variant: 0,
+                vertical,
+            };
+            if observed.insert(adjacency) {
+                adjacencies.push(adjacency);
+            }
+        }
+    }
+
+    Ok(TileSet {
+        tiles,
+        rules: Vec::new(),
+        adjacencies,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::solver::{Solver, Status};
+
+    const A: Color = Color(0xffffffff);
+    const B: Color = Color(0xff000000);
+
+    /// Two 2x2 tiles in the order A B A, where B has a black top row.
+    fn sample() -> Image {
+        Image {
+            width: 6,
+            height: 2,
+            colors: vec![A, A, B, B, A, A, A, A, A, A, A, A],
+        }
+    }
+
+    fn adjacency(first: usize, second: usize, vertical: bool) -> Adjacency {
+        Adjacency {
+            first,
+            first_variant: 0,
+            second,
+            second_variant: 0,
+            vertical,
+        }
+    }
+
+    #[test]
+    fn it_slices_the_sample_into_unique_tiles() {
+        let tile_set = infer_tile_set(&sample(), 2).unwrap();
+
+        assert_eq!(tile_set.tiles.len(), 2);
+        assert_eq!(tile_set.tiles[0].symmetry, Symmetry::X);
+        assert_eq!(tile_set.tiles[0].weight, 2);
+        assert_eq!(tile_set.tiles[1].weight, 1);
+        assert_eq!(tile_set.tiles[1].image.colors, vec![B, B, A, A]);
+    }
+
+    #[test]
+    fn it_infers_the_observed_neighbors() {
+        let tile_set = infer_tile_set(&sample(), 2).unwrap();
+
+        assert_eq!(tile_set.rules, vec![]);
+        assert_eq!(
+            tile_set.adjacencies,
+            vec![adjacency(0, 1, false), adjacency(1, 0, false)]
+        );
+    }
+
+    #[test]
+    fn it_keeps_rotated_and_asymmetric_tiles_apart() {
+        // B on top of the same tile turned upside down
+        let sample = Image {
+            width: 2,
+            height: 4,
+            colors: vec![B, B, A, A, A, A, B, B],
+        };
+
+        let tile_set = infer_tile_set(&sample, 2).unwrap();
+
+        assert_eq!(tile_set.tiles.len(), 2);
+        assert_eq!(tile_set.adjacencies, vec![adjacency(0, 1, true)]);
+
+        // a corner with arms of different lengths, whose mirror image is no rotation
+        let chiral = Image {
+            width: 3,
+            height: 3,
+            colors: vec![B, B, A, B, A, A, B, A, A],
+        };
+        assert_eq!(infer_tile_set(&chiral, 3).unwrap().tiles.len(), 1);
+    }
+
+    /// Fixes every cell of a model from the inferred tile set to the tile of the
+    /// sample and checks that no cell contradicts its neighbors.
+    fn assert_allows_the_sample(sample: &Image, tile_size: u32) {
+        let tile_set = infer_tile_set(sample, tile_size).unwrap();
+        let model = tile_set.to_model().unwrap();
+        let (columns, rows) = (sample.width / tile_size, sample.height / tile_size);
+
+        let mut image_sp = model.create_superposition(columns, rows, 1);
+        for i in 0..(columns * rows) as usize {
+            let pos = Vec2::from_index(i, columns);
+            let mut colors = Vec::new();
+            for y in 0..tile_size as i32 {
+                for x in 0..tile_size as i32 {
+                    let p = Vec2 {
+                        x: pos.x * tile_size as i32 + x,
+                        y: pos.y * tile_size as i32 + y,
+                    };
+                    colors.push(sample.get_color_at(p).unwrap());
+                }
+            }
+            // every tile has a single variant, so the tile is the color of the cell
+            let tile = tile_set
+                .tiles
+                .iter()
+                .position(|tile| tile.image.colors == colors)
+                .unwrap();
+            image_sp.fix_color_at(i, tile);
+        }
+        image_sp.propagate_all();
+
+        assert_eq!(image_sp.count_dead_pixels(), 0);
+    }
+
+    #[test]
+    fn it_allows_the_arrangement_of_the_sample() {
+        // the four corners of a frame, all rotations of the same tile
+        let corners = Image {
+            width: 4,
+            height: 4,
+            colors: vec![B, A, A, B, A, A, A, A, A, A, A, A, B, A, A, B],
+        };
+        // a black top row above white tiles
+        let column = Image {
+            width: 2,
+            height: 6,
+            colors: vec![B, B, A, A, A, A, A, A, A, A, A, A],
+        };
+
+        assert_allows_the_sample(&corners, 2);
+        assert_allows_the_sample(&column, 2);
+        assert_allows_the_sample(&sample(), 2);
+    }
+
+    #[test]
+    fn it_rejects_invalid_tile_sizes() {
+        assert_eq!(
+            infer_tile_set(&sample(), 0).err(),
+            Some(InferenceError::ZeroTileSize)
+        );
+        assert_eq!(
+            infer_tile_set(&sample(), 4).err().unwrap().to_string(),
+            "sample of 6x2 must consist of whole 4x4 tiles"
+        );
+    }
+
+    #[test]
+    fn it_never_places_sky_below_ground() {
+        let (sky, ground) = (Color(0xffffcc88), Color(0xff224466));
+        let sample = Image {
+            width: 3,
+            height: 4,
+            colors: [sky, sky, ground, ground]
+                .iter()
+                .flat_map(|&color| [color; 3])
+                .collect(),
+        };
+        let model = infer_tile_set(&sample, 1).unwrap().to_model().unwrap();
+
+        for seed in 0..10 {
+            let mut image_sp = model.create_superposition(5, 6, seed);
+            image_sp.propagate_all();
+            let mut solver = Solver::new(image_sp);
+
+            assert_eq!(solver.run(), Status::Solved, "seed {}", seed);
+            let image = model.render(&solver.image_sp);
+            for x in 0..5 {
+                for y in 1..6 {
+                    let above = image.get_color_at(Vec2 { x, y: y - 1 }).unwrap();
+                    let below = image.get_color_at(Vec2 { x, y }).unwrap();
+                    assert!(above == sky || below == ground, "seed {}", seed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn it_generates_with_the_inferred_tiles() {
+        let model = infer_tile_set(&sample(), 2).unwrap().to_model().unwrap();
+
+        let mut image_sp = model.create_superposition(5, 1, 42);
+        image_sp.propagate_all();
+        let mut solver = Solver::new(image_sp);
+
+        assert_eq!(solver.run(), Status::Solved);
+        // the white tile and the one with the black top row take turns
+        let tiles: Vec<_> = solver
+            .image_sp
+            .pixels
+            .iter()
+            .map(|p| p.colors[0].color)
+            .collect();
+        for pair in tiles.windows(2) {
+            assert_ne!(pair[0], pair[1]);
+        }
+    }
+}
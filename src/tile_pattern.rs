@@ -0,0 +1,118 @@
+use std::sync::Arc;
+
+use crate::{image::Image, pattern::Pattern, stack_set::StackSet, vec2::Vec2};
+
+pub const N: usize = 0;
+pub const W: usize = 1;
+pub const E: usize = 2;
+pub const S: usize = 3;
+
+const PATTERN_SIZE: usize = 4;
+
+const DIRS: [Vec2; PATTERN_SIZE] = [
+    Vec2 { x: 0, y: -1 },
+    Vec2 { x: -1, y: 0 },
+    Vec2 { x: 1, y: 0 },
+    Vec2 { x: 0, y: 1 },
+];
+
+/// A tile of the simple tiled model, referred to by its index. The pattern allows
+/// every tile listed for the respective direction as neighbor.
+#[derive(Clone, Debug)]
+pub struct TilePattern {
+    tile: usize,
+    weight: usize,
+    neighbors: Arc<[Vec<usize>; PATTERN_SIZE]>,
+}
+
+impl TilePattern {
+    pub fn new(tile: usize, weight: usize, neighbors: [Vec<usize>; PATTERN_SIZE]) -> Self {
+        Self {
+            tile,
+            weight,
+            neighbors: Arc::new(neighbors),
+        }
+    }
+
+    pub fn get_tile(&self) -> usize {
+        self.tile
+    }
+
+    pub fn get_neighbors_at(&self, index: usize) -> &[usize] {
+        &self.neighbors[index]
+    }
+}
+
+impl Pattern<PATTERN_SIZE> for TilePattern {
+    type Value = usize;
+
+    fn extract_pattern_at(image: &Image<usize>, pos: Vec2) -> Self {
+        let tile = image
+            .get_color_at(pos)
+            .expect("tile pattern must be inside the image");
+        let neighbors = DIRS.map(|dir| image.get_color_at(pos + dir).into_iter().collect());
+
+        TilePattern::new(tile, 1, neighbors)
+    }
+
+    fn add_neighbors(indices: &mut StackSet, index: usize, width: u32, height: u32, depth: u32) {
+        for neighbor in Self::get_neighbors(index, width, height, depth) {
+            indices.push(neighbor);
+        }
+    }
+
+    fn get_neighbors_opt(index: usize, width: u32, height: u32, _depth: u32) -> Vec<Option<usize>> {
+        let pos = Vec2::from_index(index, width);
+        DIRS.iter()
+            .map(|dir| pos + *dir)
+            .map(|p| p.is_inside(width, height).then(|| p.into_index(width)))
+            .collect()
+    }
+
+    fn get_neighbors(index: usize, width: u32, height: u32, depth: u32) -> Vec<usize> {
+        Self::get_neighbors_opt(index, width, height, depth)
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    fn get_reverse_index(index: usize) -> usize {
+        PATTERN_SIZE - 1 - index
+    }
+
+    fn matches(&self, index: usize, color: Option<usize>) -> bool {
+        match color {
+            Some(color) => self.neighbors[index].contains(&color),
+            None => true,
+        }
+    }
+
+    fn weight(&self) -> usize {
+        self.weight
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_extracts_the_observed_neighbors() {
+        let image = Image {
+            width: 2,
+            height: 2,
+            colors: vec![0, 1, 2, 3],
+        };
+
+        let pattern = TilePattern::extract_pattern_at(&image, Vec2 { x: 1, y: 0 });
+
+        assert_eq!(pattern.get_tile(), 1);
+        assert!(pattern.get_neighbors_at(N).is_empty());
+        assert_eq!(pattern.get_neighbors_at(W), &[0]);
+        assert!(pattern.get_neighbors_at(E).is_empty());
+        assert_eq!(pattern.get_neighbors_at(S), &[3]);
+        assert!(pattern.matches(S, Some(3)));
+        assert!(!pattern.matches(S, Some(2)));
+        assert!(pattern.matches(N, None));
+    }
+}
@@ -0,0 +1,571 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use ::image::ImageError;
+
+use crate::{
+    image::{PixelFormat, try_load_image, try_save_image},
+    tiled::{ACTIONS, Adjacency, NeighborRule, Symmetry, Tile, TiledError, TiledModel},
+};
+
+/// Tile weights are fractional in the tile set file, but integral in the solver.
+const WEIGHT_SCALE: f64 = 1000.0;
+
+#[derive(Debug)]
+pub enum TileSetError {
+    Io(PathBuf, std::io::Error),
+    Xml(String),
+    MissingAttribute {
+        element: String,
+        attribute: String,
+    },
+    InvalidSymmetry {
+        tile: String,
+        symmetry: String,
+    },
+    InvalidWeight {
+        tile: String,
+        weight: String,
+    },
+    MissingImage {
+        tile: String,
+        path: PathBuf,
+    },
+    InvalidImage {
+        tile: String,
+        error: ImageError,
+    },
+    TileSizeMismatch {
+        tile: String,
+        expected: u32,
+        width: u32,
+        height: u32,
+    },
+    UnknownTile(String),
+    InvalidVariant {
+        tile: String,
+        variant: String,
+    },
+    UniqueTilesUnsupported,
+}
+
+impl fmt::Display for TileSetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TileSetError::Io(path, err) => write!(f, "cannot read {}: {}", path.display(), err),
+            TileSetError::Xml(err) => write!(f, "invalid tile set xml: {}", err),
+            TileSetError::MissingAttribute { element, attribute } => {
+                write!(f, "<{}> is missing the attribute '{}'", element, attribute)
+            }
+            TileSetError::InvalidSymmetry { tile, symmetry } => {
+                write!(f, "tile '{}' has the unknown symmetry '{}'", tile, symmetry)
+            }
+            TileSetError::InvalidWeight { tile, weight } => {
+                write!(f, "tile '{}' has the invalid weight '{}'", tile, weight)
+            }
+            TileSetError::MissingImage { tile, path } => {
+                write!(
+                    f,
+                    "image {} of tile '{}' does not exist",
+                    path.display(),
+                    tile
+                )
+            }
+            TileSetError::InvalidImage { tile, error } => {
+                write!(f, "cannot access image of tile '{}': {}", tile, error)
+            }
+            TileSetError::TileSizeMismatch {
+                tile,
+                expected,
+                width,
+                height,
+            } => write!(
+                f,
+                "tile '{}' is {}x{}, expected {}x{}",
+                tile, width, height, expected, expected
+            ),
+            TileSetError::UnknownTile(name) => {
+                write!(f, "neighbor refers to unknown tile '{}'", name)
+            }
+            TileSetError::InvalidVariant { tile, variant } => {
+                write!(f, "tile '{}' has no variant '{}'", tile, variant)
+            }
+            TileSetError::UniqueTilesUnsupported => write!(f, "unique tile sets are not supported"),
+        }
+    }
+}
+
+impl std::error::Error for TileSetError {}
+
+pub struct TileSet {
+    pub tiles: Vec<Tile>,
+    pub rules: Vec<NeighborRule>,
+    pub adjacencies: Vec<Adjacency>,
+}
+
+impl TileSet {
+    pub fn to_model(&self) -> Result<TiledModel, TiledError> {
+        TiledModel::new(&self.tiles, &self.rules, &self.adjacencies)
+    }
+}
+
+/// Loads a tile set in the layout of the reference implementation's xml files:
+///
+/// ```xml
+/// <set size="14">
+///   <tiles>
+///     <tile name="corner" symmetry="L" weight="0.5"/>
+///   </tiles>
+///   <neighbors>
+///     <neighbor left="corner 1" right="corner"/>
+///   </neighbors>
+///   <adjacencies>
+///     <adjacency left="corner" right="corner 2"/>
+///     <adjacency top="corner" bottom="corner 3"/>
+///   </adjacencies>
+/// </set>
+/// ```
+///
+/// The reference implementation only knows neighbors, which also allow their rotations
+/// and reflections. Adjacencies allow the tiles in the given direction only.
+///
+/// The tile images are read from `<name>.png` in a directory named like the xml file
+/// without its extension, e.g. `tilesets/Knots/corner.png` for `tilesets/Knots.xml`,
+/// or else from the directory of the xml file itself.
+pub fn load_tile_set<T: AsRef<Path>>(path: T) -> Result<TileSet, TileSetError> {
+    let path = path.as_ref();
+    let xml = fs::read_to_string(path).map_err(|err| TileSetError::Io(path.to_path_buf(), err))?;
+    let document =
+        roxmltree::Document::parse(&xml).map_err(|err| TileSetError::Xml(err.to_string()))?;
+    let root = document.root_element();
+
+    if root
+        .attribute("unique")
+        .is_some_and(|unique| unique.eq_ignore_ascii_case("true"))
+    {
+        return Err(TileSetError::UniqueTilesUnsupported);
+    }
+
+    let tile_size = root.attribute("size").and_then(|size| size.parse().ok());
+    let xml_dir = path.parent().unwrap_or(Path::new(""));
+    let image_dirs = [
+        xml_dir.join(path.file_stem().unwrap_or_default()),
+        xml_dir.to_path_buf(),
+    ];
+
+    let mut tiles: Vec<Tile> = Vec::new();
+    for node in get_children(root, "tiles", "tile") {
+        let name = get_attribute(node, "name")?;
+
+        let symmetry = match node.attribute("symmetry").unwrap_or("X") {
+            "X" => Symmetry::X,
+            "I" => Symmetry::I,
+            "L" => Symmetry::L,
+            "T" => Symmetry::T,
+            "\\" => Symmetry::Backslash,
+            symmetry => {
+                return Err(TileSetError::InvalidSymmetry {
+                    tile: name,
+                    symmetry: String::from(symmetry),
+                });
+            }
+        };
+
+        let weight = node.attribute("weight").unwrap_or("1");
+        let weight = match weight.parse::<f64>() {
+            Ok(w) if w > 0.0 => ((w * WEIGHT_SCALE).round() as usize).max(1),
+            _ => {
+                return Err(TileSetError::InvalidWeight {
+                    tile: name,
+                    weight: String::from(weight),
+                });
+            }
+        };
+
+        let file_name = format!("{}.png", name);
+        let Some(image_path) = image_dirs
+            .iter()
+            .map(|dir| dir.join(&file_name))
+            .find(|path| path.is_file())
+        else {
+            return Err(TileSetError::MissingImage {
+                tile: name,
+                path: image_dirs[0].join(file_name),
+            });
+        };
+        let image = match try_load_image(&image_path) {
+            Ok(image) => image,
+            Err(error) => return Err(TileSetError::InvalidImage { tile: name, error }),
+        };
+
+        let expected = tile_size
+            .or(tiles.first().map(|tile| tile.image.width))
+            .unwrap_or(image.width);
+        if image.width != expected || image.height != expected {
+            return Err(TileSetError::TileSizeMismatch {
+                tile: name,
+                expected,
+                width: image.width,
+                height: image.height,
+            });
+        }
+
+        tiles.push(Tile {
+            name,
+            image,
+            symmetry,
+            weight,
+        });
+    }
+
+    let mut rules = Vec::new();
+    for node in get_children(root, "neighbors", "neighbor") {
+        let (left, left_variant) = parse_tile_reference(&tiles, &get_attribute(node, "left")?)?;
+        let (right, right_variant) = parse_tile_reference(&tiles, &get_attribute(node, "right")?)?;
+
+        rules.push(NeighborRule {
+            left,
+            left_variant,
+            right,
+            right_variant,
+        });
+    }
+
+    let mut adjacencies = Vec::new();
+    for node in get_children(root, "adjacencies", "adjacency") {
+        let vertical = node.has_attribute("top");
+        let (first, second) = if vertical {
+            ("top", "bottom")
+        } else {
+            ("left", "right")
+        };
+        let (first, first_variant) = parse_tile_reference(&tiles, &get_attribute(node, first)?)?;
+        let (second, second_variant) = parse_tile_reference(&tiles, &get_attribute(node, second)?)?;
+
+        adjacencies.push(Adjacency {
+            first,
+            first_variant,
+            second,
+            second_variant,
+            vertical,
+        });
+    }
+
+    Ok(TileSet {
+        tiles,
+        rules,
+        adjacencies,
+    })
+}
+
+/// Saves the tile set in the layout `load_tile_set` reads, with the images in a
+/// directory named like the xml file without its extension.
+pub fn save_tile_set<T: AsRef<Path>>(tile_set: &TileSet, path: T) -> Result<(), TileSetError> {
+    let path = path.as_ref();
+    let image_dir = path.with_extension("");
+    fs::create_dir_all(&image_dir).map_err(|err| TileSetError::Io(image_dir.clone(), err))?;
+
+    let size = tile_set.tiles.first().map_or(0, |tile| tile.image.width);
+    let mut xml = format!("<set size=\"{}\">\n  <tiles>\n", size);
+    for tile in &tile_set.tiles {
+        let symmetry = match tile.symmetry {
+            Symmetry::X => "X",
+            Symmetry::I => "I",
+            Symmetry::L => "L",
+            Symmetry::T => "T",
+            Symmetry::Backslash => "\\",
+        };
+        xml += &format!(
+            "    <tile name=\"{}\" symmetry=\"{}\" weight=\"{}\"/>\n",
+            escape(&tile.name),
+            symmetry,
+            tile.weight as f64 / WEIGHT_SCALE
+        );
+
+        let image_path = image_dir.join(format!("{}.png", tile.name));
+        try_save_image(&tile.image, image_path, PixelFormat::Rgba8).map_err(|error| {
+            TileSetError::InvalidImage {
+                tile: tile.name.clone(),
+                error,
+            }
+        })?;
+    }
+
+    xml += "  </tiles>\n  <neighbors>\n";
+    for rule in &tile_set.rules {
+        xml += &format!(
+            "    <neighbor left=\"{} {}\" right=\"{} {}\"/>\n",
+            escape(&tile_set.tiles[rule.left].name),
+            rule.left_variant,
+            escape(&tile_set.tiles[rule.right].name),
+            rule.right_variant
+        );
+    }
+    xml += "  </neighbors>\n  <adjacencies>\n";
+    for adjacency in &tile_set.adjacencies {
+        let (first, second) = if adjacency.vertical {
+            ("top", "bottom")
+        } else {
+            ("left", "right")
+        };
+        xml += &format!(
+            "    <adjacency {}=\"{} {}\" {}=\"{} {}\"/>\n",
+            first,
+            escape(&tile_set.tiles[adjacency.first].name),
+            adjacency.first_variant,
+            second,
+            escape(&tile_set.tiles[adjacency.second].name),
+            adjacency.second_variant
+        );
+    }
+    xml += "  </adjacencies>\n</set>\n";
+
+    fs::write(path, xml).map_err(|err| TileSetError::Io(path.to_path_buf(), err))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn get_children<'a, 'input>(
+    root: roxmltree::Node<'a, 'input>,
+    list: &'a str,
+    item: &'a str,
+) -> impl Iterator<Item = roxmltree::Node<'a, 'input>> {
+    root.children()
+        .filter(move |node| node.has_tag_name(list))
+        .flat_map(|node| node.children())
+        .filter(move |node| node.has_tag_name(item))
+}
+
+fn get_attribute(node: roxmltree::Node, attribute: &str) -> Result<String, TileSetError> {
+    node.attribute(attribute)
+        .map(String::from)
+        .ok_or_else(|| TileSetError::MissingAttribute {
+            element: String::from(node.tag_name().name()),
+            attribute: String::from(attribute),
+        })
+}
+
+/// Parses "name" or "name variant" into the tile index and variant, which refers to
+/// one of the 8 rotations and reflections like in `NeighborRule`.
+fn parse_tile_reference(tiles: &[Tile], reference: &str) -> Result<(usize, usize), TileSetError> {
+    let mut parts = reference.split_whitespace();
+    let name = parts.next().unwrap_or_default();
+    let variant = parts.next().unwrap_or("0");
+
+    let index = tiles
+        .iter()
+        .position(|tile| tile.name == name)
+        .ok_or_else(|| TileSetError::UnknownTile(String::from(name)))?;
+
+    match variant.parse::<usize>() {
+        Ok(v) if v < ACTIONS => Ok((index, v)),
+        _ => Err(TileSetError::InvalidVariant {
+            tile: String::from(name),
+            variant: String::from(variant),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{color::Color, image::Image, image::save_image};
+
+    fn write_tile_set(name: &str, xml: &str, tiles: &[&str]) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("wfc-tile-set-{}-{}", name, std::process::id()));
+        fs::create_dir_all(dir.join("set")).unwrap();
+        fs::write(dir.join("set.xml"), xml).unwrap();
+
+        for tile in tiles {
+            let image = Image {
+                width: 2,
+                height: 2,
+                colors: vec![Color(0xff000000); 4],
+            };
+            save_image(image, dir.join("set").join(format!("{}.png", tile)));
+        }
+
+        dir.join("set.xml")
+    }
+
+    #[test]
+    fn it_loads_tiles_and_neighbors() {
+        let path = write_tile_set(
+            "valid",
+            r#"<set size="2">
+                <tiles>
+                    <tile name="corner" symmetry="L" weight="0.5"/>
+                    <tile name="empty"/>
+                </tiles>
+                <neighbors>
+                    <neighbor left="corner 1" right="empty"/>
+                </neighbors>
+            </set>"#,
+            &["corner", "empty"],
+        );
+
+        let tile_set = load_tile_set(path).unwrap();
+
+        assert_eq!(tile_set.tiles.len(), 2);
+        assert_eq!(tile_set.tiles[0].symmetry, Symmetry::L);
+        assert_eq!(tile_set.tiles[0].weight, 500);
+        assert_eq!(tile_set.tiles[1].symmetry, Symmetry::X);
+        assert_eq!(
+            tile_set.rules,
+            vec![NeighborRule {
+                left: 0,
+                left_variant: 1,
+                right: 1,
+                right_variant: 0,
+            }]
+        );
+        assert_eq!(tile_set.to_model().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn it_saves_tile_sets_it_can_load_again() {
+        let path = write_tile_set(
+            "saved",
+            r#"<set size="2">
+                <tiles>
+                    <tile name="a&amp;b" symmetry="\" weight="0.25"/>
+                    <tile name="corner" symmetry="L"/>
+                </tiles>
+                <neighbors>
+                    <neighbor left="corner 3" right="a&amp;b 1"/>
+                </neighbors>
+                <adjacencies>
+                    <adjacency left="corner" right="corner 5"/>
+                    <adjacency top="a&amp;b" bottom="corner 2"/>
+                </adjacencies>
+            </set>"#,
+            &["a&b", "corner"],
+        );
+        let tile_set = load_tile_set(&path).unwrap();
+        let saved_path = path.with_file_name("saved.xml");
+
+        save_tile_set(&tile_set, &saved_path).unwrap();
+        let saved = load_tile_set(&saved_path).unwrap();
+
+        assert_eq!(saved.rules, tile_set.rules);
+        assert_eq!(saved.adjacencies.len(), 2);
+        assert_eq!(saved.adjacencies, tile_set.adjacencies);
+        assert!(saved.adjacencies[1].vertical);
+        assert_eq!(saved.tiles.len(), 2);
+        for (saved, tile) in saved.tiles.iter().zip(&tile_set.tiles) {
+            assert_eq!(saved.name, tile.name);
+            assert_eq!(saved.image, tile.image);
+            assert_eq!(saved.symmetry, tile.symmetry);
+            assert_eq!(saved.weight, tile.weight);
+        }
+    }
+
+    #[test]
+    fn it_reports_missing_images() {
+        let path = write_tile_set(
+            "missing",
+            r#"<set><tiles><tile name="a"/><tile name="b"/></tiles></set>"#,
+            &["a"],
+        );
+
+        let err = load_tile_set(path).err().unwrap();
+
+        assert!(matches!(err, TileSetError::MissingImage { ref tile, .. } if tile == "b"));
+    }
+
+    #[test]
+    fn it_reports_unknown_tiles_and_variants() {
+        let path = write_tile_set(
+            "unknown",
+            r#"<set>
+                <tiles><tile name="a" symmetry="I"/></tiles>
+                <neighbors><neighbor left="a" right="c"/></neighbors>
+            </set>"#,
+            &["a"],
+        );
+        let err = load_tile_set(path).err().unwrap();
+        assert_eq!(err.to_string(), "neighbor refers to unknown tile 'c'");
+
+        let path = write_tile_set(
+            "variant",
+            r#"<set>
+                <tiles><tile name="a" symmetry="I"/></tiles>
+                <neighbors><neighbor left="a x" right="a"/></neighbors>
+            </set>"#,
+            &["a"],
+        );
+        let err = load_tile_set(path).err().unwrap();
+        assert_eq!(err.to_string(), "tile 'a' has no variant 'x'");
+    }
+
+    #[test]
+    fn it_keeps_reflected_variants() {
+        let path = write_tile_set(
+            "reflected",
+            r#"<set>
+                <tiles><tile name="a" symmetry="I"/><tile name="b" symmetry="L"/></tiles>
+                <neighbors><neighbor left="a 3" right="b 7"/></neighbors>
+            </set>"#,
+            &["a", "b"],
+        );
+
+        let tile_set = load_tile_set(path).unwrap();
+
+        assert_eq!(
+            tile_set.rules,
+            vec![NeighborRule {
+                left: 0,
+                left_variant: 3,
+                right: 1,
+                right_variant: 7,
+            }]
+        );
+        assert!(tile_set.to_model().is_ok());
+
+        let path = write_tile_set(
+            "unreflected",
+            r#"<set>
+                <tiles><tile name="b" symmetry="L"/></tiles>
+                <neighbors><neighbor left="b 8" right="b"/></neighbors>
+            </set>"#,
+            &["b"],
+        );
+        let err = load_tile_set(path).err().unwrap();
+        assert_eq!(err.to_string(), "tile 'b' has no variant '8'");
+    }
+
+    #[test]
+    fn it_reads_images_next_to_the_xml_file() {
+        let path = write_tile_set("flat", r#"<set><tiles><tile name="a"/></tiles></set>"#, &[]);
+        let image = Image {
+            width: 2,
+            height: 2,
+            colors: vec![Color(0xff000000); 4],
+        };
+        save_image(image, path.parent().unwrap().join("a.png"));
+
+        assert_eq!(load_tile_set(&path).unwrap().tiles.len(), 1);
+    }
+
+    #[test]
+    fn it_reports_corrupt_images() {
+        let path = write_tile_set(
+            "corrupt",
+            r#"<set><tiles><tile name="a"/></tiles></set>"#,
+            &[],
+        );
+        fs::write(path.with_extension("").join("a.png"), b"not a png").unwrap();
+
+        let err = load_tile_set(path).err().unwrap();
+
+        assert!(matches!(err, TileSetError::InvalidImage { ref tile, .. } if tile == "a"));
+    }
+}
@@ -0,0 +1,501 @@
+use std::fmt;
+
+use crate::{
+    color::Color,
+    image::Image,
+    pattern::Pattern,
+    superposition::{
+        CONTRADICTION, ColorSuperposition, ImageSuperposition, PixelSuperposition, UNDECIDED,
+    },
+    tile_pattern::{E, N, S, TilePattern, W},
+    vec2::Vec2,
+};
+
+/// Rotations and reflections a variant of a tile can be referred to by.
+pub(crate) const ACTIONS: usize = 8;
+
+/// Symmetry classes of the simple tiled model, named after the letters of the same shape.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Symmetry {
+    X,
+    I,
+    L,
+    T,
+    Backslash,
+}
+
+impl Symmetry {
+    /// The number of distinct variants of a tile under rotation and reflection.
+    pub fn cardinality(&self) -> usize {
+        match self {
+            Symmetry::X => 1,
+            Symmetry::I | Symmetry::Backslash => 2,
+            Symmetry::L | Symmetry::T => 4,
+        }
+    }
+
+    fn rotate(&self, variant: usize) -> usize {
+        match self {
+            Symmetry::X => variant,
+            Symmetry::I | Symmetry::Backslash => 1 - variant,
+            Symmetry::L | Symmetry::T => (variant + 1) % 4,
+        }
+    }
+
+    fn reflect(&self, variant: usize) -> usize {
+        match self {
+            Symmetry::X | Symmetry::I => variant,
+            Symmetry::Backslash => 1 - variant,
+            Symmetry::L if variant.is_multiple_of(2) => variant + 1,
+            Symmetry::L => variant - 1,
+            Symmetry::T if variant.is_multiple_of(2) => variant,
+            Symmetry::T => 4 - variant,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Tile {
+    pub name: String,
+    pub image: Image,
+    pub symmetry: Symmetry,
+    pub weight: usize,
+}
+
+/// Allows the `left` tile in its `left_variant` to be placed directly left of the `right`
+/// tile in its `right_variant`. Tiles are referred to by index. Variants 0 to 3 count
+/// counter-clockwise rotations, 4 to 7 are the same rotations reflected, as in the
+/// reference data.xml.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NeighborRule {
+    pub left: usize,
+    pub left_variant: usize,
+    pub right: usize,
+    pub right_variant: usize,
+}
+
+/// Allows the `second` tile in its `second_variant` directly right of the `first` tile
+/// in its `first_variant`, or directly below it if `vertical`. Variants are referred to
+/// like in `NeighborRule`, but unlike a rule an adjacency is not rotated or reflected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Adjacency {
+    pub first: usize,
+    pub first_variant: usize,
+    pub second: usize,
+    pub second_variant: usize,
+    pub vertical: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TiledError {
+    TileSize {
+        tile: String,
+        expected: u32,
+        width: u32,
+        height: u32,
+    },
+    UnknownTile(usize),
+    UnknownVariant {
+        tile: String,
+        variant: usize,
+    },
+}
+
+impl fmt::Display for TiledError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TiledError::TileSize {
+                tile,
+                expected,
+                width,
+                height,
+            } => write!(
+                f,
+                "tile '{}' is {}x{}, expected {}x{}",
+                tile, width, height, expected, expected
+            ),
+            TiledError::UnknownTile(index) => write!(f, "rule refers to unknown tile {}", index),
+            TiledError::UnknownVariant { tile, variant } => {
+                write!(f, "tile '{}' has no variant {}", tile, variant)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TiledError {}
+
+/// The simple tiled model: every variant of every tile becomes a color of the
+/// superposition, with its allowed neighbors as the only pattern.
+pub struct TiledModel {
+    pub tile_size: u32,
+    bitmaps: Vec<Image>,
+    patterns: Vec<TilePattern>,
+}
+
+impl TiledModel {
+    pub fn new(
+        tiles: &[Tile],
+        rules: &[NeighborRule],
+        adjacencies: &[Adjacency],
+    ) -> Result<Self, TiledError> {
+        let tile_size = tiles.first().map_or(0, |tile| tile.image.width);
+        if let Some(tile) = tiles
+            .iter()
+            .find(|tile| tile.image.width != tile_size || tile.image.height != tile_size)
+        {
+            return Err(TiledError::TileSize {
+                tile: tile.name.clone(),
+                expected: tile_size,
+                width: tile.image.width,
+                height: tile.image.height,
+            });
+        }
+        let references = rules
+            .iter()
+            .flat_map(|rule| {
+                [
+                    (rule.left, rule.left_variant),
+                    (rule.right, rule.right_variant),
+                ]
+            })
+            .chain(adjacencies.iter().flat_map(|adjacency| {
+                [
+                    (adjacency.first, adjacency.first_variant),
+                    (adjacency.second, adjacency.second_variant),
+                ]
+            }));
+        for (index, variant) in references {
+            let tile = tiles.get(index).ok_or(TiledError::UnknownTile(index))?;
+            if variant >= ACTIONS {
+                return Err(TiledError::UnknownVariant {
+                    tile: tile.name.clone(),
+                    variant,
+                });
+            }
+        }
+
+        let mut first_variants = Vec::new();
+        let mut actions: Vec<[usize; ACTIONS]> = Vec::new();
+        let mut bitmaps = Vec::new();
+        let mut weights = Vec::new();
+        for tile in tiles {
+            let offset = actions.len();
+            first_variants.push(offset);
+
+            let symmetry = tile.symmetry;
+            let a = |variant| symmetry.rotate(variant);
+            let b = |variant| symmetry.reflect(variant);
+            for t in 0..symmetry.cardinality() {
+                let action = [
+                    t,
+                    a(t),
+                    a(a(t)),
+                    a(a(a(t))),
+                    b(t),
+                    b(a(t)),
+                    b(a(a(t))),
+                    b(a(a(a(t)))),
+                ];
+                actions.push(action.map(|variant| variant + offset));
+
+                let bitmap = match bitmaps.last() {
+                    Some(previous) if t > 0 => rotate(previous),
+                    _ => tile.image.clone(),
+                };
+                bitmaps.push(bitmap);
+                weights.push(tile.weight);
+            }
+        }
+
+        // allowed_w[t1][t2]: t2 may be placed west of t1, allowed_s[t1][t2]: t2 may be placed south of t1
+        let count = actions.len();
+        let mut allowed_w = vec![vec![false; count]; count];
+        let mut allowed_s = vec![vec![false; count]; count];
+        for rule in rules {
+            let l = actions[first_variants[rule.left]][rule.left_variant];
+            let d = actions[l][1];
+            let r = actions[first_variants[rule.right]][rule.right_variant];
+            let u = actions[r][1];
+
+            allowed_w[r][l] = true;
+            allowed_w[actions[r][6]][actions[l][6]] = true;
+            allowed_w[actions[l][4]][actions[r][4]] = true;
+            allowed_w[actions[l][2]][actions[r][2]] = true;
+
+            allowed_s[u][d] = true;
+            allowed_s[actions[d][6]][actions[u][6]] = true;
+            allowed_s[actions[u][4]][actions[d][4]] = true;
+            allowed_s[actions[d][2]][actions[u][2]] = true;
+        }
+        for adjacency in adjacencies {
+            let first = actions[first_variants[adjacency.first]][adjacency.first_variant];
+            let second = actions[first_variants[adjacency.second]][adjacency.second_variant];
+            if adjacency.vertical {
+                allowed_s[first][second] = true;
+            } else {
+                allowed_w[second][first] = true;
+            }
+        }
+
+        let patterns = (0..count)
+            .map(|t1| {
+                let neighbors = [N, W, E, S].map(|dir| {
+                    (0..count)
+                        .filter(|&t2| match dir {
+                            N => allowed_s[t2][t1],
+                            W => allowed_w[t1][t2],
+                            E => allowed_w[t2][t1],
+                            _ => allowed_s[t1][t2],
+                        })
+                        .collect()
+                });
+                TilePattern::new(t1, weights[t1], neighbors)
+            })
+            .collect();
+
+        Ok(Self {
+            tile_size,
+            bitmaps,
+            patterns,
+        })
+    }
+
+    /// The number of tile variants.
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    pub fn create_superposition(
+        &self,
+        width: u32,
+        height: u32,
+        seed: u64,
+    ) -> ImageSuperposition<4, TilePattern> {
+        let pixel_sp = PixelSuperposition {
+            colors: self
+                .patterns
+                .iter()
+                .map(|pattern| ColorSuperposition {
+                    color: pattern.get_tile(),
+                    patterns: vec![pattern.clone()],
+                    weight: pattern.weight(),
+                })
+                .collect(),
+        };
+
+        let mut image_sp = ImageSuperposition::with_seed(width, height, seed);
+        image_sp.pixels = vec![pixel_sp; (width * height) as usize];
+        image_sp
+    }
+
+    /// Blits the tile of every collapsed cell, undecided cells and contradictions are
+    /// painted like `Image::from`.
+    pub fn render(&self, image_sp: &ImageSuperposition<4, TilePattern>) -> Image {
+        let mut image = Image {
+            width: image_sp.width * self.tile_size,
+            height: image_sp.height * self.tile_size,
+            colors: vec![
+                Color(0);
+                (image_sp.width * image_sp.height * self.tile_size.pow(2)) as usize
+            ],
+        };
+
+        for (i, pixel_sp) in image_sp.pixels.iter().enumerate() {
+            let cell = Vec2::from_index(i, image_sp.width);
+            for y in 0..self.tile_size as i32 {
+                for x in 0..self.tile_size as i32 {
+                    let pos = Vec2 { x, y };
+                    let color = match pixel_sp.colors.len() {
+                        0 => CONTRADICTION,
+                        1 => self.bitmaps[pixel_sp.colors[0].color]
+                            .get_color_at(pos)
+                            .unwrap(),
+                        _ => UNDECIDED,
+                    };
+
+                    let p = Vec2 {
+                        x: cell.x * self.tile_size as i32 + x,
+                        y: cell.y * self.tile_size as i32 + y,
+                    };
+                    let index = p.into_index(image.width);
+                    image.colors[index] = color;
+                }
+            }
+        }
+
+        image
+    }
+}
+
+/// Rotates a square image counter-clockwise.
+fn rotate(image: &Image) -> Image {
+    let size = image.width as i32;
+    let mut colors = Vec::with_capacity(image.colors.len());
+    for y in 0..size {
+        for x in 0..size {
+            colors.push(
+                image
+                    .get_color_at(Vec2 {
+                        x: size - 1 - y,
+                        y: x,
+                    })
+                    .unwrap(),
+            );
+        }
+    }
+
+    Image {
+        width: image.width,
+        height: image.height,
+        colors,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::solver::{Solver, Status};
+
+    fn tile(name: &str, color: u32, symmetry: Symmetry) -> Tile {
+        Tile {
+            name: String::from(name),
+            image: Image {
+                width: 2,
+                height: 2,
+                colors: vec![Color(color); 4],
+            },
+            symmetry,
+            weight: 1,
+        }
+    }
+
+    #[test]
+    fn it_creates_a_variant_for_every_distinct_orientation() {
+        let tiles = vec![
+            tile("x", 0, Symmetry::X),
+            tile("i", 0, Symmetry::I),
+            tile("l", 0, Symmetry::L),
+            tile("t", 0, Symmetry::T),
+            tile("backslash", 0, Symmetry::Backslash),
+        ];
+
+        let model = TiledModel::new(&tiles, &[], &[]).unwrap();
+
+        assert_eq!(model.len(), 1 + 2 + 4 + 4 + 2);
+    }
+
+    #[test]
+    fn it_rotates_counter_clockwise() {
+        let image = Image {
+            width: 2,
+            height: 2,
+            colors: vec![Color(0), Color(1), Color(2), Color(3)],
+        };
+
+        let rotated = rotate(&image);
+
+        assert_eq!(rotated.colors, vec![Color(1), Color(3), Color(0), Color(2)]);
+    }
+
+    #[test]
+    fn it_only_places_tiles_with_matching_neighbors() {
+        let tiles = vec![
+            tile("a", 0xffffffff, Symmetry::X),
+            tile("b", 0xff000000, Symmetry::X),
+        ];
+        let rules = vec![NeighborRule {
+            left: 0,
+            left_variant: 0,
+            right: 0,
+            right_variant: 0,
+        }];
+        let model = TiledModel::new(&tiles, &rules, &[]).unwrap();
+
+        let mut image_sp = model.create_superposition(4, 3, 42);
+        image_sp.propagate_all();
+        let mut solver = Solver::new(image_sp);
+
+        assert_eq!(solver.run(), Status::Solved);
+        let image = model.render(&solver.image_sp);
+        assert_eq!(image.width, 8);
+        assert_eq!(image.height, 6);
+        assert!(image.colors.iter().all(|&color| color == Color(0xffffffff)));
+    }
+
+    #[test]
+    fn it_reflects_variants_from_4_on() {
+        let tiles = vec![tile("a", 0, Symmetry::X), tile("b", 0, Symmetry::L)];
+        let model = |right_variant| {
+            let rules = [NeighborRule {
+                left: 0,
+                left_variant: 0,
+                right: 1,
+                right_variant,
+            }];
+            let model = TiledModel::new(&tiles, &rules, &[]).unwrap();
+            model
+                .patterns
+                .iter()
+                .map(|pattern| [N, W, E, S].map(|dir| pattern.get_neighbors_at(dir).to_vec()))
+                .collect::<Vec<_>>()
+        };
+
+        // the reflection of the last rotation of an L tile is its second rotation
+        assert_eq!(model(7), model(2));
+        assert_ne!(model(7), model(3));
+    }
+
+    #[test]
+    fn it_keeps_the_direction_of_adjacencies() {
+        let tiles = vec![tile("a", 0, Symmetry::X), tile("b", 0, Symmetry::X)];
+        let adjacencies = [Adjacency {
+            first: 0,
+            first_variant: 0,
+            second: 1,
+            second_variant: 0,
+            vertical: true,
+        }];
+
+        let model = TiledModel::new(&tiles, &[], &adjacencies).unwrap();
+
+        let neighbors = |tile: usize| {
+            [N, W, E, S].map(|dir| model.patterns[tile].get_neighbors_at(dir).to_vec())
+        };
+        assert_eq!(neighbors(0), [vec![], vec![], vec![], vec![1]]);
+        assert_eq!(neighbors(1), [vec![0], vec![], vec![], vec![]]);
+    }
+
+    #[test]
+    fn it_rejects_invalid_tiles_and_rules() {
+        let mut tiles = vec![tile("a", 0, Symmetry::I), tile("b", 0, Symmetry::X)];
+        let rule = |left_variant, right| NeighborRule {
+            left: 0,
+            left_variant,
+            right,
+            right_variant: 0,
+        };
+
+        assert_eq!(
+            TiledModel::new(&tiles, &[rule(0, 2)], &[]).err(),
+            Some(TiledError::UnknownTile(2))
+        );
+        assert_eq!(
+            TiledModel::new(&tiles, &[rule(8, 1)], &[]).err(),
+            Some(TiledError::UnknownVariant {
+                tile: String::from("a"),
+                variant: 8
+            })
+        );
+
+        tiles[1].image.width = 1;
+        tiles[1].image.colors.truncate(2);
+        assert_eq!(
+            TiledModel::new(&tiles, &[], &[]).err().unwrap().to_string(),
+            "tile 'b' is 1x2, expected 2x2"
+        );
+    }
+}
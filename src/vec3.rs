@@ -0,0 +1,66 @@
+use std::ops::Add;
+
+/// A position in a voxel grid, `z` being the layer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vec3 {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl Add for Vec3 {
+    type Output = Vec3;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Vec3 {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z,
+        }
+    }
+}
+
+impl Vec3 {
+    pub fn from_index(index: usize, width: u32, height: u32) -> Vec3 {
+        let layer = width * height;
+        Vec3 {
+            x: (index as u32 % width) as i32,
+            y: (index as u32 % layer / width) as i32,
+            z: (index as u32 / layer) as i32,
+        }
+    }
+
+    pub fn into_index(&self, width: u32, height: u32) -> usize {
+        ((width * height) as i32 * self.z + width as i32 * self.y + self.x) as usize
+    }
+
+    pub fn is_inside(&self, width: u32, height: u32, depth: u32) -> bool {
+        self.x >= 0
+            && self.y >= 0
+            && self.z >= 0
+            && self.x < width as i32
+            && self.y < height as i32
+            && self.z < depth as i32
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_calculates_the_position_from_an_index() {
+        let pos = Vec3::from_index(123, 10, 5);
+        assert_eq!(pos, Vec3 { x: 3, y: 2, z: 2 });
+        assert_eq!(pos.into_index(10, 5), 123);
+    }
+
+    #[test]
+    fn it_ensures_to_be_inside_a_box() {
+        assert!(Vec3 { x: 0, y: 0, z: 0 }.is_inside(10, 10, 2));
+        assert!(Vec3 { x: 9, y: 9, z: 1 }.is_inside(10, 10, 2));
+        assert!(!Vec3 { x: 1, y: 1, z: 2 }.is_inside(10, 10, 2));
+        assert!(!Vec3 { x: 1, y: 1, z: -1 }.is_inside(10, 10, 2));
+        assert!(!Vec3 { x: 10, y: 1, z: 0 }.is_inside(10, 10, 2));
+    }
+}
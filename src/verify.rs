@@ -0,0 +1,151 @@
+use crate::{
+    image::Image, pattern::Pattern, ruleset::Ruleset, vec2::Vec2, vec3::Vec3, voxel::VoxelGrid,
+};
+
+/// Finds every pixel of the image whose neighborhood does not occur in the sample the
+/// ruleset was learned from. With `open_border`, anything outside of the image may be
+/// any color, otherwise it has to match the border of the sample.
+pub fn verify<const N: usize, T: Pattern<N>>(
+    ruleset: &Ruleset<N, T>,
+    image: &Image<T::Value>,
+    open_border: bool,
+) -> Vec<Vec2> {
+    find_violations(
+        ruleset,
+        &image.colors,
+        image.width,
+        image.height,
+        1,
+        open_border,
+    )
+    .into_iter()
+    .map(|pixel_index| Vec2::from_index(pixel_index, image.width))
+    .collect()
+}
+
+/// Like `verify`, but for the 3D neighborhoods of a voxel grid.
+pub fn verify_voxels<const N: usize, T: Pattern<N>>(
+    ruleset: &Ruleset<N, T>,
+    grid: &VoxelGrid<T::Value>,
+    open_border: bool,
+) -> Vec<Vec3> {
+    find_violations(
+        ruleset,
+        &grid.voxels,
+        grid.width,
+        grid.height,
+        grid.depth,
+        open_border,
+    )
+    .into_iter()
+    .map(|index| Vec3::from_index(index, grid.width, grid.height))
+    .collect()
+}
+
+fn find_violations<const N: usize, T: Pattern<N>>(
+    ruleset: &Ruleset<N, T>,
+    colors: &[T::Value],
+    width: u32,
+    height: u32,
+    depth: u32,
+    open_border: bool,
+) -> Vec<usize> {
+    let mut violations = Vec::new();
+
+    for (pixel_index, &color) in colors.iter().enumerate() {
+        let neighbors: Vec<_> = T::get_neighbors_opt(pixel_index, width, height, depth)
+            .into_iter()
+            .map(|neighbor| neighbor.map(|neighbor_index| colors[neighbor_index]))
+            .collect();
+
+        let is_valid = ruleset
+            .colors
+            .iter()
+            .filter(|color_sp| color_sp.color == color)
+            .flat_map(|color_sp| &color_sp.patterns)
+            .any(|pattern| {
+                neighbors
+                    .iter()
+                    .enumerate()
+                    .all(|(i, neighbor)| match neighbor {
+                        Some(neighbor_color) => pattern.matches(i, Some(*neighbor_color)),
+                        None => open_border || pattern.matches(i, None),
+                    })
+            });
+
+        if !is_valid {
+            violations.push(pixel_index);
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        color::Color,
+        image::load_image,
+        pattern8::Pattern8,
+        solver::{Solver, Status},
+    };
+
+    #[test]
+    fn it_accepts_a_solved_output() {
+        let sample = load_image("./test/Water.png");
+        let mut solver = Solver::<8, Pattern8>::from_sample(sample.clone(), 12, 12, 1987082627);
+        assert_eq!(solver.run(), Status::Solved);
+
+        let ruleset = Ruleset::<8, Pattern8>::extract(&sample);
+        let violations = verify(&ruleset, &Image::from(&solver.image_sp), false);
+
+        assert_eq!(violations, vec![]);
+    }
+
+    #[test]
+    fn it_reports_the_coordinates_of_violations() {
+        let (b, w) = (Color(0xff000000), Color(0xffffffff));
+        let sample = Image {
+            width: 5,
+            height: 1,
+            colors: vec![b, w, b, w, b],
+        };
+        let ruleset = Ruleset::<8, Pattern8>::extract(&sample);
+        let output = Image {
+            width: 5,
+            height: 1,
+            colors: vec![b, w, w, w, b],
+        };
+
+        let violations = verify(&ruleset, &output, false);
+
+        assert_eq!(
+            violations,
+            vec![
+                Vec2 { x: 1, y: 0 },
+                Vec2 { x: 2, y: 0 },
+                Vec2 { x: 3, y: 0 }
+            ]
+        );
+    }
+
+    #[test]
+    fn it_ignores_the_border_if_it_is_open() {
+        let (b, w) = (Color(0xff000000), Color(0xffffffff));
+        let sample = Image {
+            width: 5,
+            height: 1,
+            colors: vec![b, w, b, w, b],
+        };
+        let ruleset = Ruleset::<8, Pattern8>::extract(&sample);
+        let output = Image {
+            width: 2,
+            height: 1,
+            colors: vec![w, b],
+        };
+
+        assert_eq!(verify(&ruleset, &output, false), vec![Vec2 { x: 0, y: 0 }]);
+        assert_eq!(verify(&ruleset, &output, true), vec![]);
+    }
+}
@@ -0,0 +1,351 @@
+use std::{fmt, fs, path::Path};
+
+use crate::{
+    color::Color,
+    vec3::Vec3,
+    voxel::{EMPTY, VoxelGrid},
+};
+
+const MAGIC: [u8; 4] = *b"VOX ";
+const VERSION: u32 = 150;
+
+/// MagicaVoxel models are at most this many voxels along every axis.
+const MAX_SIZE: u32 = 256;
+
+/// Color index 0 is the empty voxel, so a model has at most 255 colors.
+const MAX_COLORS: usize = 255;
+
+#[derive(Debug)]
+pub enum VoxError {
+    Io(std::io::Error),
+    InvalidHeader,
+    Truncated,
+    NoModel,
+    VoxelOutside(Vec3),
+    TooLarge { width: u32, height: u32, depth: u32 },
+    TooManyColors(usize),
+}
+
+impl fmt::Display for VoxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VoxError::Io(err) => write!(f, "cannot access vox file: {}", err),
+            VoxError::InvalidHeader => write!(f, "not a vox file"),
+            VoxError::Truncated => write!(f, "vox file ends within a chunk"),
+            VoxError::NoModel => write!(f, "vox file has no model"),
+            VoxError::VoxelOutside(pos) => write!(
+                f,
+                "voxel at {},{},{} is outside of the model",
+                pos.x, pos.y, pos.z
+            ),
+            VoxError::TooLarge {
+                width,
+                height,
+                depth,
+            } => write!(
+                f,
+                "{}x{}x{} exceeds the maximum vox model size of {}",
+                width, height, depth, MAX_SIZE
+            ),
+            VoxError::TooManyColors(count) => write!(
+                f,
+                "{} colors do not fit into a vox palette of {}",
+                count, MAX_COLORS
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VoxError {}
+
+/// Reads the first model of a MagicaVoxel file. Empty voxels become `EMPTY`, all
+/// others the color of their palette entry. Z is up, so every layer of the grid is
+/// a horizontal slice of the model.
+pub fn parse_vox(bytes: &[u8]) -> Result<VoxelGrid, VoxError> {
+    let mut reader = Reader { bytes, pos: 0 };
+    if reader.take(4)? != MAGIC {
+        return Err(VoxError::InvalidHeader);
+    }
+    reader.u32()?;
+
+    let (id, _) = reader.chunk()?;
+    if id != *b"MAIN" {
+        return Err(VoxError::InvalidHeader);
+    }
+
+    let mut size = None;
+    let mut voxels = None;
+    let mut palette = None;
+    let mut models = 0;
+    while reader.pos < bytes.len() {
+        let (id, content) = reader.chunk()?;
+        match &id {
+            b"SIZE" => {
+                models += 1;
+                if size.is_none() {
+                    let mut content = Reader {
+                        bytes: content,
+                        pos: 0,
+                    };
+                    size = Some((content.u32()?, content.u32()?, content.u32()?));
+                }
+            }
+            b"XYZI" if voxels.is_none() => voxels = Some(content),
+            b"RGBA" => palette = Some(content),
+            _ => {}
+        }
+    }
+    if models > 1 {
+        log::warn!("vox file has {} models, using the first", models);
+    }
+
+    let (width, height, depth) = size.ok_or(VoxError::NoModel)?;
+    check_size(width, height, depth)?;
+    let palette = match palette {
+        Some(content) => read_palette(content)?,
+        None => default_palette(),
+    };
+
+    let mut grid = VoxelGrid {
+        width,
+        height,
+        depth,
+        voxels: vec![EMPTY; (width * height * depth) as usize],
+    };
+    let mut content = Reader {
+        bytes: voxels.ok_or(VoxError::NoModel)?,
+        pos: 0,
+    };
+    for _ in 0..content.u32()? {
+        let voxel: [u8; 4] = content.take(4)?.try_into().unwrap();
+        let [x, y, z, index] = voxel;
+        let pos = Vec3 {
+            x: x as i32,
+            y: y as i32,
+            z: z as i32,
+        };
+        if !pos.is_inside(width, height, depth) {
+            return Err(VoxError::VoxelOutside(pos));
+        }
+        if index > 0 {
+            grid.voxels[pos.into_index(width, height)] = palette[index as usize - 1];
+        }
+    }
+
+    Ok(grid)
+}
+
+/// Writes the grid as a single model, leaving out transparent voxels. The palette
+/// holds the other colors in the order they first occur.
+pub fn to_vox(grid: &VoxelGrid) -> Result<Vec<u8>, VoxError> {
+    let (width, height, depth) = (grid.width, grid.height, grid.depth);
+    check_size(width, height, depth)?;
+
+    let mut palette: Vec<Color> = Vec::new();
+    let mut voxels = Vec::new();
+    for (i, &color) in grid.voxels.iter().enumerate() {
+        if color.a() == 0 {
+            continue;
+        }
+
+        let index = match palette.iter().position(|&other| other == color) {
+            Some(index) => index,
+            None => {
+                palette.push(color);
+                palette.len() - 1
+            }
+        };
+        let pos = Vec3::from_index(i, width, height);
+        voxels.push([pos.x as u8, pos.y as u8, pos.z as u8, (index + 1) as u8]);
+    }
+    if palette.len() > MAX_COLORS {
+        return Err(VoxError::TooManyColors(palette.len()));
+    }
+
+    let mut size = Vec::new();
+    for value in [width, height, depth] {
+        size.extend(value.to_le_bytes());
+    }
+
+    let mut xyzi = (voxels.len() as u32).to_le_bytes().to_vec();
+    xyzi.extend(voxels.concat());
+
+    let mut rgba = vec![0; 256 * 4];
+    for (entry, color) in rgba.chunks_mut(4).zip(&palette) {
+        entry.copy_from_slice(&[color.r(), color.g(), color.b(), color.a()]);
+    }
+
+    let mut children = Vec::new();
+    write_chunk(&mut children, b"SIZE", &size, &[]);
+    write_chunk(&mut children, b"XYZI", &xyzi, &[]);
+    write_chunk(&mut children, b"RGBA", &rgba, &[]);
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(VERSION.to_le_bytes());
+    write_chunk(&mut bytes, b"MAIN", &[], &children);
+    Ok(bytes)
+}
+
+/// Fails with `TooLarge` if a model of this size does not fit into a vox file.
+pub fn check_size(width: u32, height: u32, depth: u32) -> Result<(), VoxError> {
+    if width > MAX_SIZE || height > MAX_SIZE || depth > MAX_SIZE {
+        return Err(VoxError::TooLarge {
+            width,
+            height,
+            depth,
+        });
+    }
+    Ok(())
+}
+
+pub fn load_vox<P: AsRef<Path>>(path: P) -> Result<VoxelGrid, VoxError> {
+    let bytes = fs::read(path).map_err(VoxError::Io)?;
+    parse_vox(&bytes)
+}
+
+pub fn save_vox<P: AsRef<Path>>(grid: &VoxelGrid, path: P) -> Result<(), VoxError> {
+    fs::write(path, to_vox(grid)?).map_err(VoxError::Io)
+}
+
+fn read_palette(content: &[u8]) -> Result<Vec<Color>, VoxError> {
+    if content.len() < 256 * 4 {
+        return Err(VoxError::Truncated);
+    }
+
+    Ok(content[..256 * 4]
+        .chunks(4)
+        .map(|entry| Color::from_rgba(entry[0], entry[1], entry[2], entry[3]))
+        .collect())
+}
+
+/// The palette of MagicaVoxel for files without an `RGBA` chunk, in the order of
+/// that chunk: a cube of six levels per channel without black, then ramps of the
+/// other levels in red, green, blue and gray.
+fn default_palette() -> Vec<Color> {
+    let cube = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    let ramp = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+
+    let mut palette = Vec::with_capacity(256);
+    for r in cube {
+        for g in cube {
+            for b in cube {
+                palette.push(Color::from_rgba(r, g, b, 0xff));
+            }
+        }
+    }
+    palette.pop();
+    palette.extend(ramp.map(|v| Color::from_rgba(v, 0, 0, 0xff)));
+    palette.extend(ramp.map(|v| Color::from_rgba(0, v, 0, 0xff)));
+    palette.extend(ramp.map(|v| Color::from_rgba(0, 0, v, 0xff)));
+    palette.extend(ramp.map(|v| Color::from_rgba(v, v, v, 0xff)));
+    // the chunk has room for 256 colors, but the last one is never used
+    palette.push(EMPTY);
+    palette
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]) {
+    bytes.extend(id);
+    bytes.extend((content.len() as u32).to_le_bytes());
+    bytes.extend((children.len() as u32).to_le_bytes());
+    bytes.extend(content);
+    bytes.extend(children);
+}
+
+/// Reads little endian values and chunks, failing with `Truncated` at the end.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], VoxError> {
+        let end = self.pos.checked_add(len).ok_or(VoxError::Truncated)?;
+        let bytes = self.bytes.get(self.pos..end).ok_or(VoxError::Truncated)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, VoxError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    /// Reads the id and the content of the chunk. The children directly follow the
+    /// content, so they are read as the next chunks.
+    fn chunk(&mut self) -> Result<([u8; 4], &'a [u8]), VoxError> {
+        let id = self.take(4)?.try_into().unwrap();
+        let content_len = self.u32()?;
+        self.u32()?;
+        let content = self.take(content_len as usize)?;
+        Ok((id, content))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_round_trips_a_model() {
+        let (red, blue) = (Color(0xff0000ff), Color(0xffff0000));
+        let grid = VoxelGrid {
+            width: 3,
+            height: 2,
+            depth: 2,
+            voxels: vec![
+                red, EMPTY, blue, EMPTY, EMPTY, EMPTY, //
+                blue, blue, EMPTY, EMPTY, red, EMPTY,
+            ],
+        };
+
+        let bytes = to_vox(&grid).unwrap();
+
+        assert_eq!(&bytes[0..4], b"VOX ");
+        assert_eq!(parse_vox(&bytes).unwrap(), grid);
+        assert!(matches!(
+            parse_vox(&bytes[..bytes.len() - 1]),
+            Err(VoxError::Truncated)
+        ));
+        assert!(matches!(parse_vox(b"PNG "), Err(VoxError::InvalidHeader)));
+    }
+
+    #[test]
+    fn it_rejects_more_colors_than_the_palette_holds() {
+        let grid = VoxelGrid {
+            width: 16,
+            height: 16,
+            depth: 1,
+            voxels: (0..256).map(|i| Color(0xff000000 | i)).collect(),
+        };
+
+        assert!(matches!(to_vox(&grid), Err(VoxError::TooManyColors(256))));
+    }
+
+    #[test]
+    fn it_falls_back_to_the_default_palette() {
+        let mut size = Vec::new();
+        for value in [4u32, 1, 1] {
+            size.extend(value.to_le_bytes());
+        }
+        let mut xyzi = 4u32.to_le_bytes().to_vec();
+        xyzi.extend([0, 0, 0, 1, 1, 0, 0, 36, 2, 0, 0, 216, 3, 0, 0, 255]);
+        let mut children = Vec::new();
+        write_chunk(&mut children, b"SIZE", &size, &[]);
+        write_chunk(&mut children, b"XYZI", &xyzi, &[]);
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(VERSION.to_le_bytes());
+        write_chunk(&mut bytes, b"MAIN", &[], &children);
+
+        let grid = parse_vox(&bytes).unwrap();
+
+        assert_eq!(
+            grid.voxels,
+            vec![
+                Color(0xffffffff),
+                Color(0xff0000ff),
+                Color(0xff0000ee),
+                Color(0xff111111)
+            ]
+        );
+        assert_eq!(default_palette().len(), 256);
+    }
+}
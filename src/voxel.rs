@@ -0,0 +1,226 @@
+use std::{fmt, path::Path};
+
+use ::image::ImageError;
+
+use crate::{
+    color::Color,
+    image::{Image, PixelFormat, try_load_image, try_save_image},
+    vec3::Vec3,
+};
+
+/// Written for empty voxels, e.g. transparent pixels of a slice.
+pub const EMPTY: Color = Color(0);
+
+/// The placeholder of the layer in the paths of slices.
+const LAYER_PLACEHOLDER: &str = "{z}";
+
+#[derive(Debug)]
+pub enum VoxelError {
+    Image(ImageError),
+    NoSlices,
+    /// A slice differs in size from the first one, counting from 0.
+    SliceSize {
+        layer: usize,
+        width: u32,
+        height: u32,
+    },
+    NoLayerPlaceholder(String),
+}
+
+impl fmt::Display for VoxelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VoxelError::Image(err) => write!(f, "cannot access slice: {}", err),
+            VoxelError::NoSlices => write!(f, "no slices"),
+            VoxelError::SliceSize {
+                layer,
+                width,
+                height,
+            } => write!(
+                f,
+                "slice {} is {}x{}, unlike the first slice",
+                layer, width, height
+            ),
+            VoxelError::NoLayerPlaceholder(path) => {
+                write!(f, "{} needs {} for the layer", path, LAYER_PLACEHOLDER)
+            }
+        }
+    }
+}
+
+impl std::error::Error for VoxelError {}
+
+/// A box of cells, `depth` layers of `width` x `height` cells each.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VoxelGrid<C = Color> {
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    /// Layer by layer, row by row.
+    pub voxels: Vec<C>,
+}
+
+impl<C: Copy> VoxelGrid<C> {
+    pub fn get_voxel_at(&self, pos: Vec3) -> Option<C> {
+        pos.is_inside(self.width, self.height, self.depth)
+            .then(|| self.voxels[pos.into_index(self.width, self.height)])
+    }
+
+    /// Stacks the slices, the first one being the lowest layer.
+    pub fn from_slices(slices: &[Image<C>]) -> Result<Self, VoxelError> {
+        let first = slices.first().ok_or(VoxelError::NoSlices)?;
+        if let Some(layer) = slices
+            .iter()
+            .position(|slice| (slice.width, slice.height) != (first.width, first.height))
+        {
+            return Err(VoxelError::SliceSize {
+                layer,
+                width: slices[layer].width,
+                height: slices[layer].height,
+            });
+        }
+
+        Ok(Self {
+            width: first.width,
+            height: first.height,
+            depth: slices.len() as u32,
+            voxels: slices
+                .iter()
+                .flat_map(|slice| slice.colors.iter().copied())
+                .collect(),
+        })
+    }
+
+    /// One image per layer, the lowest first.
+    pub fn to_slices(&self) -> Vec<Image<C>> {
+        let layer = (self.width * self.height) as usize;
+        if layer == 0 {
+            return Vec::new();
+        }
+
+        self.voxels
+            .chunks(layer)
+            .map(|colors| Image {
+                width: self.width,
+                height: self.height,
+                colors: colors.to_vec(),
+            })
+            .collect()
+    }
+}
+
+/// Loads one png per layer, the lowest first.
+pub fn load_slices<P: AsRef<Path>>(paths: &[P]) -> Result<VoxelGrid, VoxelError> {
+    let slices = paths
+        .iter()
+        .map(try_load_image)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(VoxelError::Image)?;
+    VoxelGrid::from_slices(&slices)
+}
+
+/// Saves every layer to the path with `{z}` replaced by the layer, e.g.
+/// `out-{z}.png`, and returns the paths.
+pub fn save_slices(grid: &VoxelGrid, path: &str) -> Result<Vec<String>, VoxelError> {
+    if !path.contains(LAYER_PLACEHOLDER) {
+        return Err(VoxelError::NoLayerPlaceholder(String::from(path)));
+    }
+
+    let mut paths = Vec::new();
+    for (z, slice) in grid.to_slices().iter().enumerate() {
+        let slice_path = path.replace(LAYER_PLACEHOLDER, &z.to_string());
+        try_save_image(slice, &slice_path, PixelFormat::Rgba8).map_err(VoxelError::Image)?;
+        paths.push(slice_path);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        pattern6::Pattern6,
+        pattern26::Pattern26,
+        ruleset::Ruleset,
+        solver::{Solver, Status},
+        superposition::ImageSuperposition,
+        verify::verify_voxels,
+    };
+
+    /// Ground below, scattered pillars and air above.
+    fn sample(size: u32) -> VoxelGrid<u8> {
+        let (air, ground, pillar) = (0, 1, 2);
+        let layer = (size * size) as usize;
+        let mut voxels = vec![ground; layer];
+        for y in 0..size {
+            for x in 0..size {
+                voxels.push(if (x * 3 + y * 5) % 7 == 0 {
+                    pillar
+                } else {
+                    air
+                });
+            }
+        }
+        voxels.extend(vec![air; layer]);
+        VoxelGrid {
+            width: size,
+            height: size,
+            depth: 3,
+            voxels,
+        }
+    }
+
+    #[test]
+    fn it_generates_voxels_with_the_same_solver() {
+        let ruleset = Ruleset::<6, Pattern6<u8>>::extract_voxels(&sample(12));
+        let mut solver = Solver::<6, Pattern6<u8>>::from_voxel_sample(sample(12), 16, 16, 3, 1);
+
+        assert_eq!(solver.run(), Status::Solved);
+        let output = solver.image_sp.to_voxels(9, 9);
+        assert_eq!(output.to_slices()[0].colors, vec![1; 16 * 16]);
+        assert_eq!(verify_voxels(&ruleset, &output, false), vec![]);
+
+        // only the inner voxels of the sample have all 26 neighbors
+        let ruleset = Ruleset::<26, Pattern26<u8>>::extract_voxels(&sample(6));
+        let mut image_sp = ImageSuperposition::<26, Pattern26<u8>>::with_depth(6, 6, 3, 1);
+        image_sp.open_border = true;
+        image_sp.extract_voxels(&sample(6));
+        image_sp.propagate_all();
+        let mut solver = Solver::new(image_sp);
+
+        assert_eq!(solver.run(), Status::Solved);
+        let output = solver.image_sp.to_voxels(9, 9);
+        assert_eq!(verify_voxels(&ruleset, &output, true), vec![]);
+    }
+
+    #[test]
+    fn it_stacks_slices() {
+        let slice = |colors: Vec<u8>| Image {
+            width: 2,
+            height: 1,
+            colors,
+        };
+
+        let grid = VoxelGrid::from_slices(&[slice(vec![1, 2]), slice(vec![3, 4])]).unwrap();
+
+        assert_eq!((grid.width, grid.height, grid.depth), (2, 1, 2));
+        assert_eq!(grid.get_voxel_at(Vec3 { x: 1, y: 0, z: 1 }), Some(4));
+        assert_eq!(grid.get_voxel_at(Vec3 { x: 0, y: 0, z: 2 }), None);
+        assert_eq!(grid.to_slices(), vec![slice(vec![1, 2]), slice(vec![3, 4])]);
+        assert!(matches!(
+            VoxelGrid::from_slices(&[
+                slice(vec![1, 2]),
+                Image::<u8> {
+                    width: 1,
+                    height: 2,
+                    colors: vec![3, 4],
+                }
+            ]),
+            Err(VoxelError::SliceSize {
+                layer: 1,
+                width: 1,
+                height: 2
+            })
+        ));
+    }
+}
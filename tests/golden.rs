@@ -0,0 +1,150 @@
+//! Solves every sample in `test/` at a fixed seed and compares the result with its
+//! golden image in `tests/golden/`. Run with `WFC_BLESS=1` to store the current
+//! results as the new golden images.
+
+use std::{env, fs, path::PathBuf};
+
+use wfc::{
+    image::{Image, load_image, save_image, try_load_image},
+    pattern8::Pattern8,
+    solver::{Solver, Status},
+};
+
+const SEED: u64 = 0;
+const BLESS_VAR: &str = "WFC_BLESS";
+
+/// The output size of every sample, small enough to solve quickly in debug builds.
+/// Samples with a closed border only solve at sizes their walls or shapes fit into.
+const SAMPLES: &[(&str, u32)] = &[
+    ("3Bricks", 10),
+    ("Angular", 12),
+    ("Cats", 12),
+    ("Chess", 12),
+    ("Dungeon", 12),
+    ("Font", 8),
+    ("Link", 12),
+    ("Nested", 14),
+    ("Skyline", 14),
+    ("Skyline2", 12),
+    ("Spirals", 11),
+    ("TrickKnot", 16),
+    ("Village", 12),
+    ("Wall", 9),
+    ("Water", 12),
+    ("c", 8),
+    ("flowers", 12),
+];
+
+/// Samples which end in a contradiction, their golden image shows where. The walls
+/// of WalledDot do not fit into any square.
+const UNSOLVABLE_SAMPLES: &[(&str, u32)] = &[("WalledDot", 12)];
+
+fn generate(name: &str, size: u32) -> (Status, Image) {
+    let sample = load_image(format!("./test/{}.png", name));
+    let mut solver = Solver::<8, Pattern8>::from_sample(sample, size, size, SEED);
+    let status = solver.run();
+    (status, Image::from(&solver.image_sp))
+}
+
+fn count_differences(actual: &Image, golden: &Image) -> Option<usize> {
+    if (actual.width, actual.height) != (golden.width, golden.height) {
+        return None;
+    }
+
+    Some(
+        actual
+            .colors
+            .iter()
+            .zip(&golden.colors)
+            .filter(|(a, g)| a != g)
+            .count(),
+    )
+}
+
+#[test]
+fn it_covers_every_sample() {
+    let mut samples: Vec<String> = fs::read_dir("./test")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "png"))
+        .map(|path| path.file_stem().unwrap().to_string_lossy().into_owned())
+        .collect();
+    samples.sort();
+
+    let mut covered: Vec<String> = SAMPLES
+        .iter()
+        .chain(UNSOLVABLE_SAMPLES)
+        .map(|(name, _)| name.to_string())
+        .collect();
+    covered.sort();
+
+    assert_eq!(
+        covered, samples,
+        "every sample in test/ needs a golden image"
+    );
+}
+
+#[test]
+fn it_matches_the_golden_images() {
+    let bless = env::var_os(BLESS_VAR).is_some();
+    let golden_dir = PathBuf::from("./tests/golden");
+    let out_dir = env::temp_dir().join(format!("wfc-golden-{}", std::process::id()));
+
+    let expected_statuses = SAMPLES.iter().map(|sample| (sample, Status::Solved)).chain(
+        UNSOLVABLE_SAMPLES
+            .iter()
+            .map(|sample| (sample, Status::Contradiction)),
+    );
+
+    let mut failures = Vec::new();
+    for (&(name, size), expected_status) in expected_statuses {
+        let (status, actual) = generate(name, size);
+        let golden_path = golden_dir.join(format!("{}.png", name));
+
+        if status != expected_status {
+            failures.push(format!(
+                "{}: {:?} instead of {:?}",
+                name, status, expected_status
+            ));
+        }
+
+        if bless {
+            save_image(actual, &golden_path);
+            continue;
+        }
+
+        let Ok(golden) = try_load_image(&golden_path) else {
+            failures.push(format!(
+                "{}: no golden image {}",
+                name,
+                golden_path.display()
+            ));
+            continue;
+        };
+
+        let out_path = out_dir.join(format!("{}.png", name));
+        let difference = match count_differences(&actual, &golden) {
+            Some(0) => continue,
+            Some(count) => format!("{} pixels differ", count),
+            None => format!(
+                "{}x{} instead of {}x{}",
+                actual.width, actual.height, golden.width, golden.height
+            ),
+        };
+        fs::create_dir_all(&out_dir).unwrap();
+        save_image(actual, &out_path);
+        failures.push(format!(
+            "{}: {}, see {}",
+            name,
+            difference,
+            out_path.display()
+        ));
+    }
+
+    assert!(
+        failures.is_empty(),
+        "golden images differ, rerun with {}=1 to bless the new results:\n{}",
+        BLESS_VAR,
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,135 @@
+//! Property tests for propagation on random tiny samples, using brute force over
+//! every possible output as the oracle.
+
+use proptest::prelude::*;
+use wfc::{
+    color::Color,
+    image::Image,
+    pattern8::Pattern8,
+    ruleset::Ruleset,
+    solver::{Solver, Status},
+    superposition::{ImageSuperposition, Wfc},
+    verify::verify,
+};
+
+const PALETTE: [Color; 3] = [Color(0xff000000), Color(0xffffffff), Color(0xff0000ff)];
+
+/// A sample of up to 4x3 pixels with up to 3 colors.
+fn sample() -> impl Strategy<Value = Image> {
+    (2..=4u32, 1..=3u32, 2..=3usize).prop_flat_map(|(width, height, color_count)| {
+        prop::collection::vec(0..color_count, (width * height) as usize).prop_map(move |indices| {
+            Image {
+                width,
+                height,
+                colors: indices.into_iter().map(|i| PALETTE[i]).collect(),
+            }
+        })
+    })
+}
+
+/// Every image of the given size which passes the validity checker.
+fn valid_completions(ruleset: &Ruleset<8, Pattern8>, width: u32, height: u32) -> Vec<Image> {
+    let colors: Vec<Color> = ruleset
+        .colors
+        .iter()
+        .map(|color_sp| color_sp.color)
+        .collect();
+    let len = (width * height) as usize;
+    let count = colors.len().pow(len as u32);
+
+    (0..count)
+        .map(|mut n| {
+            let mut image_colors = Vec::with_capacity(len);
+            for _ in 0..len {
+                image_colors.push(colors[n % colors.len()]);
+                n /= colors.len();
+            }
+            Image {
+                width,
+                height,
+                colors: image_colors,
+            }
+        })
+        .filter(|image| verify(ruleset, image, false).is_empty())
+        .collect()
+}
+
+/// Asserts that every color of every valid completion is still possible.
+fn assert_sound(image_sp: &ImageSuperposition<8, Pattern8>, completions: &[Image]) {
+    for completion in completions {
+        for (pixel_index, color) in completion.colors.iter().enumerate() {
+            let colors = &image_sp.pixels[pixel_index].colors;
+            assert!(
+                colors.iter().any(|color_sp| color_sp.color == *color),
+                "{:?} at pixel {} is part of a valid completion, but was removed",
+                color,
+                pixel_index
+            );
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn propagate_all_keeps_every_valid_completion(
+        sample in sample(),
+        width in 1..=3u32,
+        height in 1..=2u32,
+    ) {
+        let ruleset = Ruleset::<8, Pattern8>::extract(&sample);
+        let completions = valid_completions(&ruleset, width, height);
+
+        let mut image_sp = ImageSuperposition::<8, Pattern8>::with_seed(width, height, 0);
+        image_sp.apply_ruleset(&ruleset);
+        image_sp.propagate_all();
+
+        assert_sound(&image_sp, &completions);
+    }
+
+    #[test]
+    fn propagate_keeps_every_valid_completion_with_a_fixed_color(
+        sample in sample(),
+        width in 1..=3u32,
+        height in 1..=2u32,
+        pixel in any::<prop::sample::Index>(),
+        color in any::<prop::sample::Index>(),
+    ) {
+        let ruleset = Ruleset::<8, Pattern8>::extract(&sample);
+        let pixel_index = pixel.index((width * height) as usize);
+        let color = ruleset.colors[color.index(ruleset.colors.len())].color;
+        let completions: Vec<Image> = valid_completions(&ruleset, width, height)
+            .into_iter()
+            .filter(|completion| completion.colors[pixel_index] == color)
+            .collect();
+
+        let mut image_sp = ImageSuperposition::<8, Pattern8>::with_seed(width, height, 0);
+        image_sp.apply_ruleset(&ruleset);
+        image_sp.propagate_all();
+        let is_consistent =
+            image_sp.fix_color_at(pixel_index, color) && image_sp.propagate(pixel_index, &mut ());
+
+        if is_consistent {
+            assert_sound(&image_sp, &completions);
+        } else {
+            prop_assert!(completions.is_empty());
+        }
+    }
+
+    #[test]
+    fn every_solution_passes_the_validity_checker(
+        sample in sample(),
+        width in 1..=6u32,
+        height in 1..=6u32,
+        seed in any::<u64>(),
+    ) {
+        let ruleset = Ruleset::<8, Pattern8>::extract(&sample);
+        let mut solver = Solver::<8, Pattern8>::from_sample(sample, width, height, seed);
+
+        if solver.run() == Status::Solved {
+            let output = Image::from(&solver.image_sp);
+            prop_assert_eq!(verify(&ruleset, &output, false), vec![]);
+        }
+    }
+}